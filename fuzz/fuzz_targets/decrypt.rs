@@ -0,0 +1,25 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use web_nse::{decrypt, decrypt_into, decrypted_buffer_len};
+
+// The first byte is the length of the key, which is followed by the key and the ciphertext.
+fuzz_target!(|data: &[u8]| {
+    let (&key_len, data) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let (key_bytes, encrypted_data) = data.split_at((key_len as usize).min(data.len()));
+
+    if let Ok(buffer_len) = decrypted_buffer_len(encrypted_data) {
+        let mut out = vec![0; buffer_len];
+        if let Ok(data_len) = decrypt_into(encrypted_data, key_bytes, &mut out) {
+            assert!(data_len <= buffer_len);
+            assert_eq!(
+                decrypt(encrypted_data, key_bytes).unwrap(),
+                &out[..data_len]
+            );
+        }
+    } else {
+        assert!(decrypt(encrypted_data, key_bytes).is_err());
+    }
+});
@@ -0,0 +1,35 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use web_nse::fuzzing::{decrypt_block, PRIMES};
+use web_nse::Params;
+
+// The encoded parameters are followed by a byte for every prime of the derived key
+// and the encrypted block.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < Params::ENCODED_SIZE {
+        return;
+    }
+    let (params_bytes, data) = data.split_at(Params::ENCODED_SIZE);
+    let params = match Params::read(params_bytes) {
+        Some(params) => params,
+        None => return,
+    };
+    let block_size = params.block_size.bytes();
+    if data.len() < block_size {
+        return;
+    }
+    let (key, encrypted_block) = data.split_at(block_size);
+    let derived_key: Vec<u16> = key.iter().map(|&b| PRIMES[b as usize]).collect();
+    let encrypted_block_len = match params.encrypted_block_len(encrypted_block) {
+        Some(len) => len,
+        None => return,
+    };
+
+    let mut decrypted_block = vec![0; block_size];
+    let _ = decrypt_block(
+        &encrypted_block[..encrypted_block_len],
+        &derived_key,
+        &params,
+        &mut decrypted_block,
+    );
+});
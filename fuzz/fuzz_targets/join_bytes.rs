@@ -0,0 +1,29 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use web_nse::fuzzing::{join_bytes, packed_len, split_bytes, unpack_bytes};
+
+const WIDTHS: [usize; 3] = [5, 6, 8];
+const BLOCK_SIZES: [usize; 4] = [64, 128, 256, 512];
+
+// The first byte picks the element width and the block size of the packed elements,
+// the rest are the elements.
+fuzz_target!(|data: &[u8]| {
+    let (&selector, bytes) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let width = WIDTHS[selector as usize % WIDTHS.len()];
+    let bytes = &bytes[..bytes.len() - bytes.len() % width];
+    let mut elements = vec![0; bytes.len() / width];
+    join_bytes(bytes, width, &mut elements);
+    let mut split = vec![0; bytes.len()];
+    split_bytes(&mut elements.into_iter(), width, &mut split);
+    assert_eq!(split, bytes);
+
+    let block_size = BLOCK_SIZES[(selector >> 4) as usize % BLOCK_SIZES.len()];
+    if packed_len(bytes, block_size).is_some() {
+        let mut elements = vec![0; block_size];
+        unpack_bytes(bytes, &mut elements);
+    }
+});
@@ -0,0 +1,344 @@
+//! Statistical measurements of ciphertexts for comparing the cipher with others. Passing
+//! them says nothing about its security on its own; failing them points at a weakness.
+
+use crate::blocks::generation::derive_key;
+use crate::blocks::BLOCK_SIZE;
+use crate::blocks::SALT_SIZE;
+use crate::encrypt_into_with_random;
+use crate::error::{Error, Result};
+use crate::hash_number;
+use crate::normalize_key;
+use crate::padding::padded_len;
+use crate::params::Params;
+use crate::random::{RandomSource, ReplayRandom, SystemRandom};
+use crate::Blocks;
+
+/// The runs test of NIST SP 800-22, section 2.3, over the bits of some bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Runs {
+    pub ones: usize,
+    /// Number of maximal sequences of identical bits.
+    pub runs: usize,
+    /// Deviation of `runs` from its expected value for independent bits with the measured
+    /// proportion of ones, in standard deviations.
+    pub z_score: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteStats {
+    pub len: usize,
+    pub mean: f64,
+    /// Chi-square statistic of the byte frequencies against the uniform distribution,
+    /// with 255 degrees of freedom.
+    pub chi_square: f64,
+    pub runs: Runs,
+}
+
+/// Statistics of the salts, IVs (or their seeds) and elements of a ciphertext, each
+/// gathered over all of its blocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SectionStats {
+    pub salt: ByteStats,
+    pub iv: ByteStats,
+    pub elements: ByteStats,
+}
+
+/// Fractions of the output which change when a single bit of the key is flipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyAvalanche {
+    /// Fraction of the bits of `hash_number`, which sets the rotation of the data.
+    pub hash_number: f64,
+    /// Fraction of the primes of the derived key.
+    pub derive_key: f64,
+}
+
+pub fn chi_square(bytes: &[u8]) -> f64 {
+    let mut frequencies = [0usize; 256];
+    for &b in bytes {
+        frequencies[b as usize] += 1;
+    }
+    let expected = bytes.len() as f64 / 256.0;
+    frequencies
+        .iter()
+        .map(|&observed| (observed as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+pub fn runs(bytes: &[u8]) -> Runs {
+    let bits = 8 * bytes.len();
+    let bit = |i: usize| bytes[i / 8] >> (7 - i % 8) & 1;
+    let ones = bytes.iter().map(|b| b.count_ones() as usize).sum();
+    let runs = (1..bits).filter(|&i| bit(i) != bit(i - 1)).count() + (bits > 0) as usize;
+
+    let n = bits as f64;
+    let proportion = ones as f64 / n;
+    let expected = 2.0 * n * proportion * (1.0 - proportion);
+    let deviation = 2.0 * (2.0 * n).sqrt() * proportion * (1.0 - proportion);
+    Runs {
+        ones,
+        runs,
+        z_score: (runs as f64 - expected) / deviation,
+    }
+}
+
+pub fn byte_stats(bytes: &[u8]) -> ByteStats {
+    ByteStats {
+        len: bytes.len(),
+        mean: bytes.iter().map(|&b| b as f64).sum::<f64>() / bytes.len() as f64,
+        chi_square: chi_square(bytes),
+        runs: runs(bytes),
+    }
+}
+
+/// Returns the fraction of the bits of `a` and `b` which differ. Bits past the end of the
+/// shorter one count as different.
+pub fn bit_difference(a: &[u8], b: &[u8]) -> f64 {
+    let longer = a.len().max(b.len());
+    let shorter = a.len().min(b.len());
+    let different = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| (x ^ y).count_ones() as usize)
+        .sum::<usize>()
+        + 8 * (longer - shorter);
+    different as f64 / (8 * longer) as f64
+}
+
+/// Flips the `bit`-th bit of `bytes`, counting from the most significant bit of the first byte,
+/// or returns `None` if `bytes` is shorter.
+fn flip_bit(bytes: &[u8], bit: usize) -> Option<Vec<u8>> {
+    let mut flipped = bytes.to_vec();
+    *flipped.get_mut(bit / 8)? ^= 0x80 >> (bit % 8);
+    Some(flipped)
+}
+
+/// Encrypts `data` and `data` with its `bit`-th bit flipped with the same salt, IVs and padding,
+/// and returns the fraction of the bits of the ciphertexts which differ, or `None` if `data`
+/// has no `bit`-th bit.
+pub fn plaintext_avalanche(
+    params: &Params,
+    data: &[u8],
+    key_bytes: &[u8],
+    bit: usize,
+) -> Result<Option<f64>> {
+    let flipped_data = match flip_bit(data, bit) {
+        Some(flipped_data) => flipped_data,
+        None => return Ok(None),
+    };
+    let padded_len = padded_len(params, data.len())?;
+    let blocks = padded_len / params.block_size.bytes();
+    let mut salt = [0; SALT_SIZE];
+    // Leaves room for rejected IVs.
+    let mut ivs = vec![0; 4 * blocks * params.iv_size()];
+    let mut padding = vec![0; padded_len - data.len()];
+    let mut random = SystemRandom::new();
+    random.fill(&mut salt)?;
+    random.fill(&mut ivs)?;
+    random.fill(&mut padding)?;
+
+    let encrypt = |data: &[u8]| -> Result<Vec<u8>> {
+        let mut encrypted_data = vec![0; params.encrypted_len(data.len())?];
+        let mut random = ReplayRandom::new(&salt, &ivs, &padding);
+        let encrypted_length =
+            encrypt_into_with_random(params, data, key_bytes, &mut encrypted_data, &mut random)?;
+        encrypted_data.truncate(encrypted_length);
+        Ok(encrypted_data)
+    };
+    Ok(Some(bit_difference(
+        &encrypt(data)?,
+        &encrypt(&flipped_data)?,
+    )))
+}
+
+/// Measures how much of `hash_number` and `derive_key` changes when the `bit`-th bit of
+/// the key is flipped, or returns `None` if the key has no `bit`-th bit.
+pub fn key_avalanche(key_bytes: &[u8], salt: &[u8], bit: usize) -> Option<KeyAvalanche> {
+    let flipped_key_bytes = flip_bit(key_bytes, bit)?;
+    let (key, flipped_key) = (normalize_key(key_bytes), normalize_key(&flipped_key_bytes));
+
+    let mut derived_key = [0; BLOCK_SIZE];
+    let mut flipped_derived_key = [0; BLOCK_SIZE];
+    derive_key(key, salt, &mut derived_key);
+    derive_key(flipped_key, salt, &mut flipped_derived_key);
+    let changed_primes = derived_key
+        .iter()
+        .zip(flipped_derived_key.iter())
+        .filter(|(p, q)| p != q)
+        .count();
+
+    Some(KeyAvalanche {
+        hash_number: bit_difference(&hash_number(key), &hash_number(flipped_key)),
+        derive_key: changed_primes as f64 / BLOCK_SIZE as f64,
+    })
+}
+
+/// Splits a ciphertext of any format into its salts, IVs and elements, and measures every
+/// section of every block on its own, as `SectionStats` describes. The header format shares
+/// one salt among all blocks, so it is measured again for each of them.
+pub fn section_stats(encrypted_data: &[u8]) -> Result<Vec<SectionStats>> {
+    let blocks = Blocks::parse(encrypted_data)?;
+    let mut stats = Vec::with_capacity(blocks.count);
+    for_each_block(&blocks, |block_salt, iv, elements| {
+        let salt = match (&blocks.header, block_salt) {
+            (_, Some(salt)) => salt,
+            (Some(header), None) => &header.salt[..],
+            (None, None) => &[],
+        };
+        stats.push(SectionStats {
+            salt: byte_stats(salt),
+            iv: byte_stats(iv),
+            elements: byte_stats(elements),
+        });
+    })?;
+    Ok(stats)
+}
+
+/// Measures every section of a ciphertext with the blocks merged, which summarizes
+/// `section_stats` with more bytes per statistic. A shared salt is measured once.
+pub fn merged_section_stats(encrypted_data: &[u8]) -> Result<SectionStats> {
+    let blocks = Blocks::parse(encrypted_data)?;
+    let mut salt = Vec::new();
+    let mut iv = Vec::new();
+    let mut elements = Vec::new();
+    if let Some(header) = &blocks.header {
+        salt.extend_from_slice(&header.salt);
+    }
+    for_each_block(&blocks, |block_salt, block_iv, block_elements| {
+        salt.extend_from_slice(block_salt.unwrap_or(&[]));
+        iv.extend_from_slice(block_iv);
+        elements.extend_from_slice(block_elements);
+    })?;
+
+    Ok(SectionStats {
+        salt: byte_stats(&salt),
+        iv: byte_stats(&iv),
+        elements: byte_stats(&elements),
+    })
+}
+
+/// Calls `f` with the salt, IV and elements of every block in order. Only blocks of the
+/// 0.1 format have a salt of their own.
+fn for_each_block(blocks: &Blocks, mut f: impl FnMut(Option<&[u8]>, &[u8], &[u8])) -> Result<()> {
+    let mut position = 0;
+    for _ in 0..blocks.count {
+        let block_len = blocks
+            .block_len(&blocks.bytes[position..])
+            .ok_or(Error::InvalidCiphertext)?;
+        let block = &blocks.bytes[position..position + block_len];
+        position += block_len;
+
+        let (salt, block, iv_size) = match &blocks.header {
+            Some(header) => (None, block, header.params.iv_size()),
+            None => (Some(&block[..SALT_SIZE]), &block[SALT_SIZE..], BLOCK_SIZE),
+        };
+        f(salt, &block[..iv_size], &block[iv_size..]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::ELEMENT_SIZE;
+    use crate::blocks::LEGACY_ENCRYPTED_BLOCK_SIZE;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn chi_square_measures_byte_frequencies() {
+        let uniform: Vec<u8> = (0..1024).map(|i| i as u8).collect();
+        assert_eq!(chi_square(&uniform), 0.0);
+        // Every byte but one is missing.
+        assert_eq!(chi_square(&[7; 1024]), 255.0 * 1024.0);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn runs_counts_sequences_of_identical_bits() {
+        let alternating = runs(&[0b0101_0101; 4]);
+        assert_eq!(alternating.ones, 16);
+        assert_eq!(alternating.runs, 32);
+        assert!(alternating.z_score > 3.0);
+
+        assert_eq!(runs(&[0b1111_0000, 0b0000_1111]).runs, 3);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn bit_difference_counts_missing_bits() {
+        assert_eq!(bit_difference(&[0xff, 0], &[0x0f, 0]), 0.25);
+        assert_eq!(bit_difference(&[1], &[1, 2]), 0.5);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn flipping_a_bit_changes_the_ciphertext() {
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let avalanche = plaintext_avalanche(&Params::new(), &data, b"key", 9)
+            .unwrap()
+            .unwrap();
+        assert!(avalanche > 0.1);
+
+        let key_avalanche = key_avalanche(b"key", &[0; SALT_SIZE], 3).unwrap();
+        assert!(key_avalanche.hash_number > 0.3 && key_avalanche.hash_number < 0.7);
+        assert!(key_avalanche.derive_key > 0.9);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn avalanche_of_a_missing_bit_is_none() {
+        assert_eq!(
+            plaintext_avalanche(&Params::new(), &[], b"key", 0),
+            Ok(None)
+        );
+        assert_eq!(
+            plaintext_avalanche(&Params::new(), &[1], b"key", 8),
+            Ok(None)
+        );
+        assert_eq!(key_avalanche(&[], &[0; SALT_SIZE], 0), None);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn merged_section_stats_split_legacy_blocks() {
+        let stats = merged_section_stats(&[0; 2 * LEGACY_ENCRYPTED_BLOCK_SIZE]).unwrap();
+        assert_eq!(stats.salt.len, 2 * SALT_SIZE);
+        assert_eq!(stats.iv.len, 2 * BLOCK_SIZE);
+        assert_eq!(stats.elements.len, 2 * ELEMENT_SIZE * BLOCK_SIZE);
+
+        let encrypted_data = crate::encrypt(&[1; 500], b"key").unwrap();
+        let stats = merged_section_stats(&encrypted_data).unwrap();
+        assert_eq!(stats.salt.len, SALT_SIZE);
+        assert_eq!(stats.iv.len, 2 * BLOCK_SIZE);
+        assert_eq!(stats.elements.len, 2 * ELEMENT_SIZE * BLOCK_SIZE);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn section_stats_single_out_a_zeroed_block() {
+        let mut encrypted_data = crate::encrypt(&[1; 1000], b"key").unwrap();
+        let stats = section_stats(&encrypted_data).unwrap();
+        assert_eq!(stats.len(), 4);
+        assert!(stats.iter().all(|block| block.salt.len == SALT_SIZE));
+
+        // Zeroes the elements of the third block.
+        let block_len = |block: &SectionStats| block.iv.len + block.elements.len;
+        let blocks_len: usize = stats.iter().map(block_len).sum();
+        let elements_end =
+            encrypted_data.len() - blocks_len + stats[..3].iter().map(block_len).sum::<usize>();
+        encrypted_data[elements_end - stats[2].elements.len..elements_end].fill(0);
+
+        let stats = section_stats(&encrypted_data).unwrap();
+        assert_eq!(stats[2].elements.runs.ones, 0);
+        // The elements are never uniform, but the others are alike.
+        assert!(stats
+            .iter()
+            .enumerate()
+            .all(|(i, block)| i == 2
+                || 10.0 * block.elements.chi_square < stats[2].elements.chi_square));
+    }
+}
@@ -1,71 +1,252 @@
-use super::*;
-use crate::blocks::BLOCK_SIZE;
-use crate::blocks::ELEMENT_SIZE;
-use js_sys::Uint8Array;
-
-fn u40_as_i40(u40: u64) -> i64 {
-    let half = 1 << (8 * ELEMENT_SIZE - 1);
-    if u40 < half {
-        u40 as i64
-    } else {
-        u40 as i64 - 2 * half as i64
-    }
-}
+use crate::blocks::max_element_magnitude;
 
-fn i40_as_u40(i40: i64) -> u64 {
-    let half = 1 << (8 * ELEMENT_SIZE - 1);
-    if i40 < 0 {
-        (i40 + 2 * half) as u64
-    } else {
-        i40 as u64
-    }
+/// Sign-extends the `width` bytes wide two's complement number stored in `value`.
+fn from_twos_complement(value: u64, width: usize) -> i64 {
+    let unused_bits = 64 - 8 * width;
+    ((value << unused_bits) as i64) >> unused_bits
 }
 
-pub fn join_bytes(bytes: &[u8], data: &mut [i64]) {
-    for i in (0..bytes.iter().count()).step_by(ELEMENT_SIZE) {
-        let mut element = 0;
-        for j in (i..i + ELEMENT_SIZE).rev() {
-            element <<= 8;
-            element += bytes[j] as u64;
-        }
+pub fn join_bytes(bytes: &[u8], width: usize, data: &mut [i64]) {
+    for i in (0..bytes.len()).step_by(width) {
+        let mut element = [0; 8];
+        element[..width].copy_from_slice(&bytes[i..i + width]);
 
-        let index = i / ELEMENT_SIZE;
-        data[index] = u40_as_i40(element);
+        let index = i / width;
+        data[index] = from_twos_complement(u64::from_le_bytes(element), width);
     }
 }
 
-pub fn split_bytes<I>(data: &mut I, bytes: &Uint8Array)
+/// Stores every element in `width` bytes of two's complement. The elements have to fit,
+/// which `Params::is_supported` guarantees.
+pub fn split_bytes<I>(data: &mut I, width: usize, bytes: &mut [u8])
 where
     I: Iterator<Item = i64>,
 {
-    for i in (0..bytes.length()).step_by(ELEMENT_SIZE) {
-        let mut e = i40_as_u40(data.next().unwrap());
+    for i in (0..bytes.len()).step_by(width) {
+        let e = data.next().unwrap();
+        debug_assert_eq!(from_twos_complement(e as u64, width), e);
 
-        for j in i..i + ELEMENT_SIZE as u32 {
-            bytes.set_index(j, (e & 255) as u8);
-            e >>= 8;
-        }
-        if e > 1 {
-            panic!("overflow");
+        bytes[i..i + width].copy_from_slice(&e.to_le_bytes()[..width]);
+    }
+}
+
+fn zigzag(element: i64) -> u64 {
+    ((element << 1) ^ (element >> 63)) as u64
+}
+
+fn unzigzag(element: u64) -> i64 {
+    (element >> 1) as i64 ^ -((element & 1) as i64)
+}
+
+/// Narrowest width `pack_bytes` writes and `packed_len` accepts, so that a block of
+/// `block_size` bytes always takes at least `packed_size(MIN_PACKED_WIDTH, block_size)` bytes.
+/// Encrypted blocks are practically never this narrow, so the floor rarely costs anything.
+pub const MIN_PACKED_WIDTH: usize = 16;
+
+/// Returns the widest zigzag-encoded element of a block of `block_size` bytes,
+/// which is the widest element `unpack_bytes` accepts.
+pub const fn max_packed_width(block_size: usize) -> usize {
+    64 - (2 * max_element_magnitude(block_size)).leading_zeros() as usize
+}
+
+/// Returns the number of bytes `pack_bytes` writes for `block_size` elements `width` bits wide.
+pub const fn packed_size(width: usize, block_size: usize) -> usize {
+    1 + (block_size * width + 7) / 8
+}
+
+/// Writes the zigzag-encoded elements with as many bits each as the widest of them needs,
+/// but at least `MIN_PACKED_WIDTH`, preceded by that width. Returns the number of bytes written.
+pub fn pack_bytes(data: &[i64], bytes: &mut [u8]) -> usize {
+    let width = data
+        .iter()
+        .map(|&e| 64 - zigzag(e).leading_zeros() as usize)
+        .fold(MIN_PACKED_WIDTH, usize::max);
+    debug_assert!(width <= max_packed_width(data.len()));
+    bytes[0] = width as u8;
+
+    let mut buffer = 0u128;
+    let mut buffered_bits = 0;
+    let mut index = 1;
+    for &e in data {
+        buffer |= (zigzag(e) as u128) << buffered_bits;
+        buffered_bits += width;
+        while buffered_bits >= 8 {
+            bytes[index] = buffer as u8;
+            index += 1;
+            buffer >>= 8;
+            buffered_bits -= 8;
         }
     }
+    if buffered_bits > 0 {
+        bytes[index] = buffer as u8;
+        index += 1;
+    }
+    index
 }
 
-#[wasm_bindgen_test]
-pub fn join_bytes_can_be_reversed() {
-    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
-    let data: [i64; BLOCK_SIZE] = [274877906943; BLOCK_SIZE];
-    let mut bytes = Uint8Array::new_with_length(BLOCK_SIZE as u32 * ELEMENT_SIZE as u32);
-    split_bytes(&mut data.iter().map(|&x| x as i64), &mut bytes);
-    let mut joined_bytes = [0; BLOCK_SIZE];
-    join_bytes(&bytes.to_vec(), &mut joined_bytes);
+/// Returns the number of bytes of `block_size` packed elements at the beginning of `bytes`,
+/// or `None` if they are truncated, narrower than `MIN_PACKED_WIDTH` or wider than
+/// `max_packed_width`.
+pub fn packed_len(bytes: &[u8], block_size: usize) -> Option<usize> {
+    let width = *bytes.first()? as usize;
+    if width < MIN_PACKED_WIDTH || width > max_packed_width(block_size) {
+        return None;
+    }
+    let size = packed_size(width, block_size);
+    if bytes.len() < size {
+        return None;
+    }
+    Some(size)
+}
+
+/// Reverses `pack_bytes`. `bytes` has to be validated with `packed_len` first.
+pub fn unpack_bytes(bytes: &[u8], data: &mut [i64]) {
+    let width = bytes[0] as usize;
+    let mask = (1u128 << width) - 1;
+
+    let mut buffer = 0u128;
+    let mut buffered_bits = 0;
+    let mut index = 1;
+    for e in data.iter_mut() {
+        while buffered_bits < width {
+            buffer |= (bytes[index] as u128) << buffered_bits;
+            index += 1;
+            buffered_bits += 8;
+        }
+        *e = unzigzag((buffer & mask) as u64);
+        buffer >>= width;
+        buffered_bits -= width;
+    }
 }
 
-#[wasm_bindgen_test]
-pub fn u40_as_i40_can_be_reversed() {
-    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
-    let u40 = 560608505551;
-    let i40 = u40_as_i40(u40);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::BLOCK_SIZE;
+    use crate::blocks::ELEMENT_SIZE;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
 
-    assert_eq!(u40, i40_as_u40(i40));
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn join_bytes_can_be_reversed() {
+        let data: [i64; BLOCK_SIZE] = [274877906943; BLOCK_SIZE];
+        let mut bytes = [0; BLOCK_SIZE * ELEMENT_SIZE];
+        split_bytes(&mut data.iter().copied(), ELEMENT_SIZE, &mut bytes);
+        let mut joined_bytes = [0; BLOCK_SIZE];
+        join_bytes(&bytes, ELEMENT_SIZE, &mut joined_bytes);
+        assert_eq!(joined_bytes, data);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn from_twos_complement_sign_extends() {
+        assert_eq!(from_twos_complement(560608505551, 5), -538903122225);
+        assert_eq!(from_twos_complement(274877906943, 5), 274877906943);
+        assert_eq!(from_twos_complement(0xffff_ffff_ffff, 6), -1);
+        assert_eq!(from_twos_complement(u64::MAX, 8), -1);
+    }
+
+    #[test]
+    fn join_bytes_reverses_split_bytes_for_every_width() {
+        let mut data = [0; BLOCK_SIZE];
+        for (i, e) in data.iter_mut().enumerate() {
+            *e = (i as i64 - 128) * (max_element_magnitude(BLOCK_SIZE) as i64 / 128);
+        }
+        for &width in [5, 6, 8].iter() {
+            let mut bytes = [0; BLOCK_SIZE * 8];
+            split_bytes(
+                &mut data.iter().copied(),
+                width,
+                &mut bytes[..BLOCK_SIZE * width],
+            );
+            let mut joined_bytes = [0; BLOCK_SIZE];
+            join_bytes(&bytes[..BLOCK_SIZE * width], width, &mut joined_bytes);
+            assert_eq!(joined_bytes[..], data[..]);
+        }
+    }
+
+    #[test]
+    fn zigzag_can_be_reversed() {
+        for &e in [
+            0,
+            1,
+            -1,
+            2,
+            -2,
+            274877906943,
+            -274877906944,
+            i64::MAX,
+            i64::MIN,
+        ]
+        .iter()
+        {
+            assert_eq!(unzigzag(zigzag(e)), e);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+
+    #[test]
+    fn unpack_bytes_reverses_pack_bytes() {
+        let mut data = [0; BLOCK_SIZE];
+        for (i, e) in data.iter_mut().enumerate() {
+            *e = (i as i64 - 128) * 1_000_003;
+        }
+        let mut bytes = [0; BLOCK_SIZE * ELEMENT_SIZE + 1];
+        let size = pack_bytes(&data, &mut bytes);
+        assert_eq!(bytes[0], 28);
+        assert_eq!(size, packed_size(28, BLOCK_SIZE));
+        assert_eq!(packed_len(&bytes[..size], BLOCK_SIZE), Some(size));
+
+        let mut unpacked = [0; BLOCK_SIZE];
+        unpack_bytes(&bytes[..size], &mut unpacked);
+        assert_eq!(unpacked[..], data[..]);
+    }
+
+    #[test]
+    fn pack_bytes_of_zeros_writes_min_packed_width() {
+        let mut bytes = [1; 1 + 2 * BLOCK_SIZE];
+        let size = pack_bytes(&[0; BLOCK_SIZE], &mut bytes);
+        assert_eq!(bytes[0] as usize, MIN_PACKED_WIDTH);
+        assert_eq!(size, packed_size(MIN_PACKED_WIDTH, BLOCK_SIZE));
+
+        let mut unpacked = [1; BLOCK_SIZE];
+        unpack_bytes(&bytes[..size], &mut unpacked);
+        assert_eq!(unpacked[..], [0; BLOCK_SIZE][..]);
+    }
+
+    #[test]
+    fn packed_len_rejects_widths_pack_bytes_never_writes() {
+        let bytes = [0; BLOCK_SIZE * ELEMENT_SIZE + 1];
+        for width in 0..MIN_PACKED_WIDTH {
+            let mut bytes = bytes;
+            bytes[0] = width as u8;
+            assert_eq!(packed_len(&bytes, BLOCK_SIZE), None);
+        }
+    }
+
+    #[test]
+    fn packed_len_rejects_truncated_and_too_wide_elements() {
+        let mut bytes = [0; BLOCK_SIZE * ELEMENT_SIZE + 1];
+        bytes[0] = 20;
+        let size = packed_size(20, BLOCK_SIZE);
+        assert_eq!(packed_len(&bytes[..size - 1], BLOCK_SIZE), None);
+        assert_eq!(packed_len(&bytes, BLOCK_SIZE), Some(size));
+        assert_eq!(packed_len(&bytes, 64), Some(packed_size(20, 64)));
+
+        bytes[0] = max_packed_width(BLOCK_SIZE) as u8 + 1;
+        assert_eq!(packed_len(&bytes, BLOCK_SIZE), None);
+        assert_eq!(packed_len(&[], BLOCK_SIZE), None);
+    }
+
+    #[test]
+    fn max_packed_width_grows_with_block_size() {
+        assert_eq!(max_packed_width(64), 38);
+        assert_eq!(max_packed_width(BLOCK_SIZE), 40);
+        assert_eq!(max_packed_width(512), 41);
+    }
 }
@@ -2,19 +2,12 @@ mod vectors;
 
 use crate::blocks::generation::vectors::are_orthogonal;
 use crate::blocks::generation::vectors::vector_difference;
-use crate::blocks::BLOCK_SIZE;
+use crate::blocks::IV_SEED_SIZE;
+use crate::blocks::MAX_BLOCK_SIZE;
+use crate::error::Result;
+use crate::random::RandomSource;
 use hkdf::Hkdf;
-use js_sys::Uint8Array;
-use num_bigint::BigUint;
 use sha2::Sha256;
-use wasm_bindgen::prelude::wasm_bindgen;
-use wasm_bindgen_test::*;
-
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = ["window", "crypto"])]
-    fn getRandomValues(typedArray: Uint8Array) -> Uint8Array;
-}
 
 pub const PRIMES: [u16; 256] = [
     2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
@@ -34,86 +27,194 @@ pub const PRIMES: [u16; 256] = [
     1619,
 ];
 
-pub fn derive_key(key: &BigUint, salt: &[u8], derived_key: &mut [u16; BLOCK_SIZE]) {
-    let hkdf = Hkdf::<Sha256>::new(Some(salt), &key.to_bytes_be());
-    let mut okm = [0; BLOCK_SIZE];
-    hkdf.expand(&[], &mut okm).unwrap();
-    for i in 0..BLOCK_SIZE {
-        derived_key[i] = PRIMES[okm[i] as usize];
+pub const MAX_PRIME: u16 = PRIMES[PRIMES.len() - 1];
+
+/// Derives the prime vectors of all blocks of a message from a single HKDF extraction,
+/// using the block counter as the HKDF info.
+pub struct BlockKeys {
+    hkdf: Hkdf<Sha256>,
+}
+
+impl BlockKeys {
+    pub fn new(key: &[u8], salt: &[u8]) -> BlockKeys {
+        BlockKeys {
+            hkdf: Hkdf::<Sha256>::new(Some(salt), key),
+        }
+    }
+
+    /// Fills the whole `derived_key`, which is as long as the block.
+    pub fn derive(&self, counter: u64, derived_key: &mut [u16]) {
+        expand_primes(&self.hkdf, &counter.to_be_bytes(), derived_key);
     }
 }
 
-pub fn generate_iv(derived_key: &[u16; BLOCK_SIZE], block: &[i8], iv: &mut [i8; BLOCK_SIZE]) {
-    let mut unsigned_iv;
-    let mut difference = [0; BLOCK_SIZE];
+/// Derives the prime vector of a block which carries its own salt, as in the 0.1 format.
+pub fn derive_key(key: &[u8], salt: &[u8], derived_key: &mut [u16]) {
+    let hkdf = Hkdf::<Sha256>::new(Some(salt), key);
+    expand_primes(&hkdf, &[], derived_key);
+}
+
+fn expand_primes(hkdf: &Hkdf<Sha256>, info: &[u8], derived_key: &mut [u16]) {
+    let mut okm = [0; MAX_BLOCK_SIZE];
+    let okm = &mut okm[..derived_key.len()];
+    hkdf.expand(info, okm).unwrap();
+    for i in 0..derived_key.len() {
+        #[cfg(not(feature = "constant-time"))]
+        {
+            derived_key[i] = PRIMES[okm[i] as usize];
+        }
+        #[cfg(feature = "constant-time")]
+        {
+            derived_key[i] = crate::constant_time::lookup(&PRIMES, okm[i]);
+        }
+    }
+}
+
+pub fn generate_iv<R: RandomSource>(
+    derived_key: &[u16],
+    block: &[i8],
+    iv: &mut [i8],
+    random: &mut R,
+) -> Result<()> {
+    let block_size = block.len();
+    let mut unsigned_iv = [0; MAX_BLOCK_SIZE];
+    let unsigned_iv = &mut unsigned_iv[..block_size];
+    let mut difference = [0; MAX_BLOCK_SIZE];
+    let difference = &mut difference[..block_size];
+    // The number of draws leaks even with the `constant-time` feature, see `constant_time`.
     while {
-        unsigned_iv = getRandomValues(Uint8Array::new_with_length(BLOCK_SIZE as u32));
+        random.fill_iv(unsigned_iv)?;
 
-        for i in 0..BLOCK_SIZE {
-            iv[i] = unsigned_iv.get_index(i as u32) as i8;
+        for i in 0..block_size {
+            iv[i] = unsigned_iv[i] as i8;
         }
 
-        vector_difference(block, iv, &mut difference);
+        vector_difference(block, iv, difference);
 
-        are_orthogonal(derived_key, &difference)
+        are_orthogonal(derived_key, difference)
     } {}
+    Ok(())
 }
 
-#[wasm_bindgen_test]
-pub fn generate_iv_derived_key_is_not_orthogonal_with_block_and_key_difference() {
-    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
-    let derived_key = [1; BLOCK_SIZE];
-
-    let unsigned_block: [u8; BLOCK_SIZE] = [
-        237, 252, 84, 64, 120, 86, 39, 29, 40, 209, 77, 44, 108, 122, 150, 132, 46, 92, 98, 25,
-        173, 186, 243, 142, 77, 145, 76, 71, 245, 118, 52, 172, 221, 109, 180, 222, 235, 18, 182,
-        237, 67, 240, 184, 164, 150, 90, 193, 97, 89, 74, 204, 205, 185, 255, 80, 49, 97, 172, 213,
-        235, 96, 46, 24, 104, 68, 32, 179, 110, 229, 157, 134, 6, 94, 199, 82, 118, 185, 155, 160,
-        157, 240, 102, 63, 131, 3, 195, 152, 146, 202, 243, 217, 208, 3, 93, 180, 20, 164, 129,
-        112, 207, 162, 16, 69, 220, 173, 220, 211, 162, 84, 14, 167, 182, 91, 110, 178, 214, 31,
-        152, 103, 133, 191, 213, 244, 226, 49, 21, 15, 36, 21, 122, 54, 114, 121, 210, 134, 219, 7,
-        220, 110, 12, 111, 66, 28, 104, 217, 18, 120, 177, 188, 145, 244, 194, 16, 187, 34, 147,
-        164, 94, 247, 204, 192, 54, 143, 155, 66, 191, 225, 159, 88, 20, 25, 12, 72, 178, 212, 178,
-        61, 85, 108, 223, 38, 187, 44, 187, 197, 138, 143, 180, 248, 48, 132, 157, 193, 104, 196,
-        186, 26, 198, 214, 182, 119, 65, 187, 161, 108, 234, 95, 112, 36, 19, 42, 194, 95, 18, 45,
-        154, 203, 218, 118, 20, 185, 197, 197, 85, 42, 147, 251, 18, 197, 192, 107, 156, 191, 115,
-        194, 207, 162, 16, 69, 220, 173, 220, 211, 162, 84, 14, 167, 182, 91, 110, 178, 214, 31,
-        152, 103, 133,
-    ];
-    let mut block = Vec::new();
-
-    for i in 0..BLOCK_SIZE {
-        block.push(unsigned_block[i] as i8);
-    }
+/// Like `generate_iv`, but draws a short seed and expands it into the IV,
+/// drawing a new seed whenever the IV would be rejected.
+pub fn generate_seeded_iv<R: RandomSource>(
+    derived_key: &[u16],
+    block: &[i8],
+    seed: &mut [u8; IV_SEED_SIZE],
+    iv: &mut [i8],
+    random: &mut R,
+) -> Result<()> {
+    let mut difference = [0; MAX_BLOCK_SIZE];
+    let difference = &mut difference[..block.len()];
+    while {
+        random.fill_iv(seed)?;
+        expand_iv_seed(seed, iv);
 
-    let mut iv = [0; BLOCK_SIZE];
-    generate_iv(&derived_key, &block.to_vec(), &mut iv);
+        vector_difference(block, iv, difference);
 
-    let mut difference = [0; BLOCK_SIZE];
-    vector_difference(&block, &iv, &mut difference);
+        are_orthogonal(derived_key, difference)
+    } {}
+    Ok(())
+}
 
-    assert!(!are_orthogonal(&derived_key, &difference));
+/// Fills the whole `iv`, which is as long as the block.
+pub fn expand_iv_seed(seed: &[u8], iv: &mut [i8]) {
+    let hkdf = Hkdf::<Sha256>::new(None, seed);
+    let mut unsigned_iv = [0; MAX_BLOCK_SIZE];
+    let unsigned_iv = &mut unsigned_iv[..iv.len()];
+    hkdf.expand(b"iv", unsigned_iv).unwrap();
+    for i in 0..iv.len() {
+        iv[i] = unsigned_iv[i] as i8;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blocks::BLOCK_SIZE;
     use crate::blocks::SALT_SIZE;
+    use crate::random::SystemRandom;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn generate_iv_derived_key_is_not_orthogonal_with_block_and_key_difference() {
+        let derived_key = [1; BLOCK_SIZE];
+
+        let unsigned_block: [u8; BLOCK_SIZE] = [
+            237, 252, 84, 64, 120, 86, 39, 29, 40, 209, 77, 44, 108, 122, 150, 132, 46, 92, 98, 25,
+            173, 186, 243, 142, 77, 145, 76, 71, 245, 118, 52, 172, 221, 109, 180, 222, 235, 18,
+            182, 237, 67, 240, 184, 164, 150, 90, 193, 97, 89, 74, 204, 205, 185, 255, 80, 49, 97,
+            172, 213, 235, 96, 46, 24, 104, 68, 32, 179, 110, 229, 157, 134, 6, 94, 199, 82, 118,
+            185, 155, 160, 157, 240, 102, 63, 131, 3, 195, 152, 146, 202, 243, 217, 208, 3, 93,
+            180, 20, 164, 129, 112, 207, 162, 16, 69, 220, 173, 220, 211, 162, 84, 14, 167, 182,
+            91, 110, 178, 214, 31, 152, 103, 133, 191, 213, 244, 226, 49, 21, 15, 36, 21, 122, 54,
+            114, 121, 210, 134, 219, 7, 220, 110, 12, 111, 66, 28, 104, 217, 18, 120, 177, 188,
+            145, 244, 194, 16, 187, 34, 147, 164, 94, 247, 204, 192, 54, 143, 155, 66, 191, 225,
+            159, 88, 20, 25, 12, 72, 178, 212, 178, 61, 85, 108, 223, 38, 187, 44, 187, 197, 138,
+            143, 180, 248, 48, 132, 157, 193, 104, 196, 186, 26, 198, 214, 182, 119, 65, 187, 161,
+            108, 234, 95, 112, 36, 19, 42, 194, 95, 18, 45, 154, 203, 218, 118, 20, 185, 197, 197,
+            85, 42, 147, 251, 18, 197, 192, 107, 156, 191, 115, 194, 207, 162, 16, 69, 220, 173,
+            220, 211, 162, 84, 14, 167, 182, 91, 110, 178, 214, 31, 152, 103, 133,
+        ];
+        let block: Vec<i8> = unsigned_block.iter().map(|&b| b as i8).collect();
+
+        let mut iv = [0; BLOCK_SIZE];
+        generate_iv(&derived_key, &block, &mut iv, &mut SystemRandom::new()).unwrap();
+
+        let mut difference = [0; BLOCK_SIZE];
+        vector_difference(&block, &iv, &mut difference);
+
+        assert!(!are_orthogonal(&derived_key, &difference));
+    }
 
     #[test]
     fn derive_key_fills_output_with_primes() {
-        use num_bigint::RandBigInt;
         use rand::{thread_rng, RngCore};
         let mut rng = thread_rng();
 
-        let key = rng.gen_biguint(128);
+        let mut key = [0; 16];
+        rng.fill_bytes(&mut key);
         let mut salt = [0; SALT_SIZE];
         rng.fill_bytes(&mut salt);
 
         let mut derived_key = [0; BLOCK_SIZE];
         derive_key(&key, &salt, &mut derived_key);
         for e in derived_key.iter() {
-            assert_eq!(PRIMES.iter().any(|p| p == e), true);
+            assert!(PRIMES.contains(e));
         }
     }
+
+    #[test]
+    fn expand_iv_seed_is_deterministic() {
+        let mut iv1 = [0; BLOCK_SIZE];
+        expand_iv_seed(&[3; IV_SEED_SIZE], &mut iv1);
+        let mut iv2 = [0; BLOCK_SIZE];
+        expand_iv_seed(&[3; IV_SEED_SIZE], &mut iv2);
+        let mut iv3 = [0; BLOCK_SIZE];
+        expand_iv_seed(&[4; IV_SEED_SIZE], &mut iv3);
+
+        assert_eq!(iv1[..], iv2[..]);
+        assert_ne!(iv1[..], iv3[..]);
+    }
+
+    #[test]
+    fn block_keys_differ_between_counters() {
+        let block_keys = BlockKeys::new(&[1, 2, 3], &[4; SALT_SIZE]);
+
+        let mut derived_key1 = [0; BLOCK_SIZE];
+        block_keys.derive(0, &mut derived_key1);
+        let mut derived_key2 = [0; BLOCK_SIZE];
+        block_keys.derive(1, &mut derived_key2);
+        let mut derived_key3 = [0; BLOCK_SIZE];
+        block_keys.derive(0, &mut derived_key3);
+
+        assert_ne!(derived_key1[..], derived_key2[..]);
+        assert_eq!(derived_key1[..], derived_key3[..]);
+    }
 }
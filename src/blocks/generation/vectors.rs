@@ -2,11 +2,15 @@ pub fn are_orthogonal(vector1: &[u16], vector2: &[i16]) -> bool {
     if vector1.len() != vector2.len() {
         panic!("vectors are of different size");
     }
-    vector1
+    let dot_product = vector1
         .iter()
         .zip(vector2)
-        .fold(0i64, |acc, (&e1, &e2)| acc + e1 as i64 * e2 as i64)
-        == 0
+        .fold(0i64, |acc, (&e1, &e2)| acc + e1 as i64 * e2 as i64);
+    #[cfg(not(feature = "constant-time"))]
+    return dot_product == 0;
+    // A rejected IV is still redrawn, but it is as likely for every key and never stored.
+    #[cfg(feature = "constant-time")]
+    return crate::constant_time::is_zero(dot_product);
 }
 
 pub fn vector_difference(vector1: &[i8], vector2: &[i8], output: &mut [i16]) {
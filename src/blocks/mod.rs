@@ -1,47 +1,116 @@
-mod bytes;
-mod generation;
+pub(crate) mod bytes;
+pub(crate) mod generation;
+#[cfg(test)]
+mod reference;
 
 use crate::blocks::bytes::join_bytes;
+pub use crate::blocks::bytes::max_packed_width;
+use crate::blocks::bytes::pack_bytes;
+pub use crate::blocks::bytes::packed_len;
+pub use crate::blocks::bytes::packed_size;
 use crate::blocks::bytes::split_bytes;
+use crate::blocks::bytes::unpack_bytes;
+pub use crate::blocks::bytes::MIN_PACKED_WIDTH;
 use crate::blocks::generation::derive_key;
+use crate::blocks::generation::expand_iv_seed;
 use crate::blocks::generation::generate_iv;
-use js_sys::Uint8Array;
-use num_bigint::BigUint;
-use wasm_bindgen::prelude::wasm_bindgen;
-use wasm_bindgen_test::*;
+use crate::blocks::generation::generate_seeded_iv;
+pub use crate::blocks::generation::BlockKeys;
+use crate::blocks::generation::MAX_PRIME;
+use crate::error::Error;
+use crate::error::Result;
+use crate::params::ElementCodec;
+use crate::params::ElementWidth;
+use crate::params::IvMode;
+use crate::params::Params;
+use crate::random::RandomSource;
 
+/// Block size of the 0.1 format and of the default parameters.
 pub const BLOCK_SIZE: usize = 256;
-const SALT_SIZE: usize = 16;
+/// Largest block size of any parameters, which sizes the buffers of a block.
+pub const MAX_BLOCK_SIZE: usize = 512;
+pub const SALT_SIZE: usize = 16;
+
+/// Element width of the 0.1 format, in bytes.
+pub const ELEMENT_SIZE: usize = 5;
 
-const ELEMENT_SIZE: usize = 5;
+/// Returns an upper bound of `|r * sum1 - p * sum2|` over all blocks of `block_size` bytes,
+/// IVs and derived keys.
+///
+/// With `|r| <= 128`, `|r - iv| <= 255` and every prime at most `MAX_PRIME`,
+/// `sum1 <= block_size * MAX_PRIME^2` and `|sum2| <= 2 * 255 * block_size * MAX_PRIME`,
+/// so an element is at most `(128 + 510) * block_size * MAX_PRIME^2` in magnitude.
+pub const fn max_element_magnitude(block_size: usize) -> u64 {
+    let max_prime = MAX_PRIME as u64;
+    (128 + 2 * 255) * block_size as u64 * max_prime * max_prime
+}
 
-pub const ENCRYPTED_BLOCK_SIZE: usize = ELEMENT_SIZE * BLOCK_SIZE + BLOCK_SIZE + SALT_SIZE;
+// Every element of the 0.1 format fits its width.
+const _: () = assert!(max_element_magnitude(BLOCK_SIZE) <= ElementWidth::Bytes5.max_magnitude());
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = ["window", "crypto"])]
-    fn getRandomValues(typedArray: Uint8Array) -> Uint8Array;
+/// Returns an upper bound of the numerator `|e * sum2 + p * (sum1 * sum2 - sum3)|` which
+/// `decrypt_block` divides by `sum2^2`, for a block of `block_size` elements of any `i64`.
+///
+/// With `sum2 <= block_size * MAX_PRIME^2`, `|sum1| <= 2 * 128 * block_size * MAX_PRIME`
+/// and `|sum3| <= 2 * 2^63 * block_size * MAX_PRIME`, the numerator is at most
+/// `2^63 * sum2 + MAX_PRIME * (|sum1| * sum2 + |sum3|)`.
+pub const fn max_decryption_numerator(block_size: usize) -> u128 {
+    let max_prime = MAX_PRIME as u128;
+    let max_element = 1 << 63;
+    let sum1 = 2 * 128 * block_size as u128 * max_prime;
+    let sum2 = block_size as u128 * max_prime * max_prime;
+    let sum3 = 2 * max_element * block_size as u128 * max_prime;
+    max_element * sum2 + max_prime * (sum1 * sum2 + sum3)
 }
 
-pub fn encrypt_block(block: &[i8], key: &BigUint, encrypted_block: &Uint8Array) {
-    let salt = getRandomValues(Uint8Array::new_with_length(SALT_SIZE as u32));
-    for i in 0..salt.length() {
-        encrypted_block.set_index(i, salt.get_index(i));
-    }
+// The decryption of a block of any parameters is exact in `i128`, whatever its elements.
+const _: () = assert!(max_decryption_numerator(MAX_BLOCK_SIZE) <= i128::MAX as u128);
 
-    let mut derived_key = [0; BLOCK_SIZE];
-    derive_key(key, &salt.to_vec(), &mut derived_key);
+/// Size of the seed stored instead of the IV in `IvMode::Seeded`.
+pub const IV_SEED_SIZE: usize = 16;
+
+pub const ENCRYPTED_BLOCK_SIZE: usize = ELEMENT_SIZE * BLOCK_SIZE + BLOCK_SIZE;
+
+/// Size of a block in the 0.1 format, which stores its own salt in front of the IV.
+pub const LEGACY_ENCRYPTED_BLOCK_SIZE: usize = SALT_SIZE + ENCRYPTED_BLOCK_SIZE;
 
-    let mut iv = [0; BLOCK_SIZE];
-    generate_iv(&derived_key, &block.to_vec(), &mut iv);
-    for i in SALT_SIZE..SALT_SIZE + BLOCK_SIZE {
-        encrypted_block.set_index(i as u32, iv[i - SALT_SIZE] as u8);
+/// Encrypts a block of `params.block_size` bytes with a derived key as long as the block.
+/// Returns the number of bytes written to `encrypted_block`.
+pub fn encrypt_block<R: RandomSource>(
+    unsigned_block: &[u8],
+    derived_key: &[u16],
+    params: &Params,
+    encrypted_block: &mut [u8],
+    random: &mut R,
+) -> Result<usize> {
+    let block_size = params.block_size.bytes();
+    let mut block = [0; MAX_BLOCK_SIZE];
+    let block = &mut block[..block_size];
+    for i in 0..block_size {
+        block[i] = unsigned_block[i] as i8;
     }
 
+    let mut iv = [0; MAX_BLOCK_SIZE];
+    let iv = &mut iv[..block_size];
+    match params.iv_mode {
+        IvMode::Full => {
+            generate_iv(derived_key, block, iv, random)?;
+            for i in 0..block_size {
+                encrypted_block[i] = iv[i] as u8;
+            }
+        }
+        IvMode::Seeded => {
+            let mut seed = [0; IV_SEED_SIZE];
+            generate_seeded_iv(derived_key, block, &mut seed, iv, random)?;
+            encrypted_block[..IV_SEED_SIZE].copy_from_slice(&seed);
+        }
+    }
+    let iv_size = params.iv_size();
+
     let mut sum1 = 0;
     let mut sum2 = 0;
 
-    for i in 0..BLOCK_SIZE {
+    for i in 0..block_size {
         sum1 += derived_key[i] as i64 * derived_key[i] as i64;
         sum2 += derived_key[i] as i64 * (block[i] as i64 - iv[i] as i64);
     }
@@ -51,110 +120,350 @@ pub fn encrypt_block(block: &[i8], key: &BigUint, encrypted_block: &Uint8Array)
         .iter()
         .zip(derived_key.iter())
         .map(|(&r, &p)| r as i64 * sum1 - (p as i64 * sum2));
-    split_bytes(
-        &mut encrypted_block_iter,
-        &encrypted_block.subarray(
-            SALT_SIZE as u32 + BLOCK_SIZE as u32,
-            ENCRYPTED_BLOCK_SIZE as u32,
-        ),
-    );
+    let elements = &mut encrypted_block[iv_size..params.encrypted_block_size()];
+    match params.element_codec {
+        ElementCodec::Fixed => {
+            let width = params.element_width.bytes();
+            split_bytes(
+                &mut encrypted_block_iter,
+                width,
+                &mut elements[..width * block_size],
+            );
+            Ok(iv_size + width * block_size)
+        }
+        ElementCodec::Packed => {
+            let mut data = [0; MAX_BLOCK_SIZE];
+            let data = &mut data[..block_size];
+            for (d, e) in data.iter_mut().zip(encrypted_block_iter) {
+                *d = e;
+            }
+            Ok(iv_size + pack_bytes(data, elements))
+        }
+    }
 }
 
-pub fn decrypt_block(encrypted_block: &Uint8Array, key: &BigUint, decrypted_block: &mut [i8]) {
-    let salt = encrypted_block.subarray(0, SALT_SIZE as u32);
-
-    let mut derived_key = [0; BLOCK_SIZE];
-    derive_key(key, &salt.to_vec(), &mut derived_key);
+/// Reads the IV and the elements of an encrypted block validated with
+/// `Params::encrypted_block_len`.
+pub fn read_encrypted_block(
+    encrypted_block: &[u8],
+    params: &Params,
+    iv: &mut [i8],
+    elements: &mut [i64],
+) {
+    let block_size = params.block_size.bytes();
+    match params.iv_mode {
+        IvMode::Full => {
+            for i in 0..block_size {
+                iv[i] = encrypted_block[i] as i8;
+            }
+        }
+        IvMode::Seeded => expand_iv_seed(&encrypted_block[..IV_SEED_SIZE], iv),
+    }
 
-    let unsigned_iv =
-        encrypted_block.subarray(SALT_SIZE as u32, SALT_SIZE as u32 + BLOCK_SIZE as u32);
-    let mut iv = [0; BLOCK_SIZE];
-    for i in 0..BLOCK_SIZE {
-        iv[i] = unsigned_iv.get_index(i as u32) as i8;
+    let encoded_elements = &encrypted_block[params.iv_size()..];
+    match params.element_codec {
+        ElementCodec::Fixed => {
+            let width = params.element_width.bytes();
+            join_bytes(&encoded_elements[..width * block_size], width, elements)
+        }
+        ElementCodec::Packed => unpack_bytes(encoded_elements, elements),
     }
+}
 
-    let mut joined_encrypted_block: [i64; BLOCK_SIZE] = [0; BLOCK_SIZE];
+/// Decrypts an encrypted block validated with `Params::encrypted_block_len`. Fails on elements
+/// no block encrypted with `params` can contain.
+pub fn decrypt_block(
+    encrypted_block: &[u8],
+    derived_key: &[u16],
+    params: &Params,
+    decrypted_block: &mut [u8],
+) -> Result<()> {
+    let block_size = params.block_size.bytes();
+    let mut iv = [0; MAX_BLOCK_SIZE];
+    let iv = &mut iv[..block_size];
+    let mut joined_encrypted_block = [0; MAX_BLOCK_SIZE];
+    let joined_encrypted_block = &mut joined_encrypted_block[..block_size];
+    read_encrypted_block(encrypted_block, params, iv, joined_encrypted_block);
 
-    join_bytes(
-        &encrypted_block
-            .subarray(
-                BLOCK_SIZE as u32 + SALT_SIZE as u32,
-                ENCRYPTED_BLOCK_SIZE as u32,
-            )
-            .to_vec(),
-        &mut joined_encrypted_block,
-    );
+    let max_magnitude = max_element_magnitude(block_size);
+    if joined_encrypted_block
+        .iter()
+        .any(|e| e.unsigned_abs() > max_magnitude)
+    {
+        return Err(Error::InvalidCiphertext);
+    }
 
-    let mut sum1 = 0;
-    let mut sum2 = 0;
-    let mut sum3 = 0;
+    let mut sum1 = 0i128;
+    let mut sum2 = 0i128;
+    let mut sum3 = 0i128;
 
-    for i in 0..BLOCK_SIZE {
-        sum1 += derived_key[i] as i64 * iv[i] as i64;
-        sum2 += derived_key[i] as i64 * derived_key[i] as i64;
-        sum3 += derived_key[i] as i64 * joined_encrypted_block[i] as i64;
+    for i in 0..block_size {
+        let p = derived_key[i] as i128;
+        sum1 += p * iv[i] as i128;
+        sum2 += p * p;
+        sum3 += p * joined_encrypted_block[i] as i128;
     }
 
     sum1 <<= 1;
     sum3 <<= 1;
 
-    for i in 0..BLOCK_SIZE {
-        let a = joined_encrypted_block[i] + derived_key[i] as i64 * sum1;
-        let b = (derived_key[i] as i64 * (sum3 / sum2))
-            + ((derived_key[i] as i64 * (sum3 % sum2)) / sum2);
-        let c = (a - b) / sum2;
-        decrypted_block[i] = c as i8;
+    // `r_i = (e_i + p_i * sum1 - p_i * sum3 / sum2) / sum2`, multiplied through by `sum2`
+    // to divide only once, see `max_decryption_numerator`.
+    let offset = sum1 * sum2 - sum3;
+    let denominator = sum2 * sum2;
+    #[cfg(not(feature = "constant-time"))]
+    for i in 0..block_size {
+        let numerator = joined_encrypted_block[i] as i128 * sum2 + derived_key[i] as i128 * offset;
+        if numerator.rem_euclid(denominator) != 0 {
+            return Err(Error::InvalidCiphertext);
+        }
+        let r = numerator.div_euclid(denominator);
+        if r < i8::MIN as i128 || r > i8::MAX as i128 {
+            return Err(Error::InvalidCiphertext);
+        }
+        decrypted_block[i] = r as i8 as u8;
+    }
+    // Divides every element, and fails only afterwards so as not to reveal which one
+    // did not decrypt.
+    #[cfg(feature = "constant-time")]
+    {
+        let mut valid = true;
+        for i in 0..block_size {
+            let numerator =
+                joined_encrypted_block[i] as i128 * sum2 + derived_key[i] as i128 * offset;
+            let (r, exact) = crate::constant_time::div_exact_i8(numerator, denominator);
+            valid &= exact;
+            decrypted_block[i] = r as u8;
+        }
+        if !valid {
+            return Err(Error::InvalidCiphertext);
+        }
     }
+    Ok(())
 }
 
-#[wasm_bindgen_test]
-pub fn encrypt_block_can_be_reversed() {
-    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+pub fn decrypt_legacy_block(
+    encrypted_block: &[u8],
+    key: &[u8],
+    decrypted_block: &mut [u8],
+) -> Result<()> {
+    let mut derived_key = [0; BLOCK_SIZE];
+    derive_key(key, &encrypted_block[..SALT_SIZE], &mut derived_key);
 
-    use std::str::FromStr;
-    let unsigned_block: [u8; BLOCK_SIZE] = [
-        237, 252, 84, 64, 120, 86, 39, 29, 40, 209, 77, 44, 108, 122, 150, 132, 46, 92, 98, 25,
-        173, 186, 243, 142, 77, 145, 76, 71, 245, 118, 52, 172, 221, 109, 180, 222, 235, 18, 182,
-        237, 67, 240, 184, 164, 150, 90, 193, 97, 89, 74, 204, 205, 185, 255, 80, 49, 97, 172, 213,
-        235, 96, 46, 24, 104, 68, 32, 179, 110, 229, 157, 134, 6, 94, 199, 82, 118, 185, 155, 160,
-        157, 240, 102, 63, 131, 3, 195, 152, 146, 202, 243, 217, 208, 3, 93, 180, 20, 164, 129,
-        112, 207, 162, 16, 69, 220, 173, 220, 211, 162, 84, 14, 167, 182, 91, 110, 178, 214, 31,
-        152, 103, 133, 191, 213, 244, 226, 49, 21, 15, 36, 21, 122, 54, 114, 121, 210, 134, 219, 7,
-        220, 110, 12, 111, 66, 28, 104, 217, 18, 120, 177, 188, 145, 244, 194, 16, 187, 34, 147,
-        164, 94, 247, 204, 192, 54, 143, 155, 66, 191, 225, 159, 88, 20, 25, 12, 72, 178, 212, 178,
-        61, 85, 108, 223, 38, 187, 44, 187, 197, 138, 143, 180, 248, 48, 132, 157, 193, 104, 196,
-        186, 26, 198, 214, 182, 119, 65, 187, 161, 108, 234, 95, 112, 36, 19, 42, 194, 95, 18, 45,
-        154, 203, 218, 118, 20, 185, 197, 197, 85, 42, 147, 251, 18, 197, 192, 107, 156, 191, 115,
-        194, 207, 162, 16, 69, 220, 173, 220, 211, 162, 84, 14, 167, 182, 91, 110, 178, 214, 31,
-        152, 103, 133,
-    ];
-    let mut block = Vec::new();
+    decrypt_block(
+        &encrypted_block[SALT_SIZE..LEGACY_ENCRYPTED_BLOCK_SIZE],
+        &derived_key,
+        &Params::legacy(),
+        decrypted_block,
+    )
+}
 
-    for i in 0..BLOCK_SIZE {
-        block.push(unsigned_block[i] as i8);
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::BlockSize;
+    use crate::random::SystemRandom;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
 
-    let key = BigUint::from_str("110192826829776194000614388426091705128").unwrap();
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
 
-    let mut encrypted_block = Uint8Array::new_with_length(ENCRYPTED_BLOCK_SIZE as u32);
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn encrypt_block_can_be_reversed() {
+        let unsigned_block: [u8; BLOCK_SIZE] = [
+            237, 252, 84, 64, 120, 86, 39, 29, 40, 209, 77, 44, 108, 122, 150, 132, 46, 92, 98, 25,
+            173, 186, 243, 142, 77, 145, 76, 71, 245, 118, 52, 172, 221, 109, 180, 222, 235, 18,
+            182, 237, 67, 240, 184, 164, 150, 90, 193, 97, 89, 74, 204, 205, 185, 255, 80, 49, 97,
+            172, 213, 235, 96, 46, 24, 104, 68, 32, 179, 110, 229, 157, 134, 6, 94, 199, 82, 118,
+            185, 155, 160, 157, 240, 102, 63, 131, 3, 195, 152, 146, 202, 243, 217, 208, 3, 93,
+            180, 20, 164, 129, 112, 207, 162, 16, 69, 220, 173, 220, 211, 162, 84, 14, 167, 182,
+            91, 110, 178, 214, 31, 152, 103, 133, 191, 213, 244, 226, 49, 21, 15, 36, 21, 122, 54,
+            114, 121, 210, 134, 219, 7, 220, 110, 12, 111, 66, 28, 104, 217, 18, 120, 177, 188,
+            145, 244, 194, 16, 187, 34, 147, 164, 94, 247, 204, 192, 54, 143, 155, 66, 191, 225,
+            159, 88, 20, 25, 12, 72, 178, 212, 178, 61, 85, 108, 223, 38, 187, 44, 187, 197, 138,
+            143, 180, 248, 48, 132, 157, 193, 104, 196, 186, 26, 198, 214, 182, 119, 65, 187, 161,
+            108, 234, 95, 112, 36, 19, 42, 194, 95, 18, 45, 154, 203, 218, 118, 20, 185, 197, 197,
+            85, 42, 147, 251, 18, 197, 192, 107, 156, 191, 115, 194, 207, 162, 16, 69, 220, 173,
+            220, 211, 162, 84, 14, 167, 182, 91, 110, 178, 214, 31, 152, 103, 133,
+        ];
+        let key = 110192826829776194000614388426091705128u128.to_be_bytes();
+        let block_keys = BlockKeys::new(&key, &[7; SALT_SIZE]);
 
-    encrypt_block(&block, &key, &mut encrypted_block);
+        for &block_size in [
+            BlockSize::Bytes64,
+            BlockSize::Bytes128,
+            BlockSize::Bytes256,
+            BlockSize::Bytes512,
+        ]
+        .iter()
+        {
+            let mut derived_key = [0; MAX_BLOCK_SIZE];
+            let derived_key = &mut derived_key[..block_size.bytes()];
+            block_keys.derive(0, derived_key);
+            let mut block = [0; MAX_BLOCK_SIZE];
+            let block = &mut block[..block_size.bytes()];
+            for (b, &u) in block.iter_mut().zip(unsigned_block.iter().cycle()) {
+                *b = u;
+            }
 
-    let mut decrypted_block = [1; BLOCK_SIZE];
+            for &(iv_mode, element_codec) in [
+                (IvMode::Full, ElementCodec::Fixed),
+                (IvMode::Seeded, ElementCodec::Fixed),
+                (IvMode::Full, ElementCodec::Packed),
+                (IvMode::Seeded, ElementCodec::Packed),
+            ]
+            .iter()
+            {
+                for &element_width in [
+                    ElementWidth::Bytes5,
+                    ElementWidth::Bytes6,
+                    ElementWidth::Bytes8,
+                ]
+                .iter()
+                {
+                    let params = Params {
+                        block_size,
+                        iv_mode,
+                        element_codec,
+                        element_width,
+                        ..Params::new()
+                    };
+                    if !params.is_supported() {
+                        continue;
+                    }
+                    let mut encrypted_block = [0; MAX_BLOCK_SIZE + 8 * MAX_BLOCK_SIZE];
 
-    decrypt_block(&encrypted_block, &key, &mut decrypted_block);
+                    let encrypted_length = encrypt_block(
+                        block,
+                        derived_key,
+                        &params,
+                        &mut encrypted_block,
+                        &mut SystemRandom::new(),
+                    )
+                    .unwrap();
+                    assert_eq!(
+                        params.encrypted_block_len(&encrypted_block),
+                        Some(encrypted_length)
+                    );
 
-    for (e1, e2) in block.iter().zip(decrypted_block.iter()) {
-        assert_eq!(e1, e2);
-    }
-}
+                    let mut decrypted_block = [1; MAX_BLOCK_SIZE];
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+                    decrypt_block(
+                        &encrypted_block[..encrypted_length],
+                        derived_key,
+                        &params,
+                        &mut decrypted_block,
+                    )
+                    .unwrap();
+
+                    assert_eq!(block[..], decrypted_block[..block_size.bytes()]);
+                }
+            }
+        }
+    }
 
     #[test]
     fn encrypted_block_size_is_1552() {
-        assert_eq!(ENCRYPTED_BLOCK_SIZE, 1552);
+        assert_eq!(LEGACY_ENCRYPTED_BLOCK_SIZE, 1552);
+    }
+
+    #[test]
+    fn encrypted_block_size_without_salt_is_1536() {
+        assert_eq!(ENCRYPTED_BLOCK_SIZE, 1536);
+    }
+
+    #[test]
+    fn max_element_magnitude_bounds_the_worst_block() {
+        assert_eq!(max_element_magnitude(BLOCK_SIZE), 428_108_983_808);
+
+        // The largest primes with the block and IV at opposite extremes come close to the bound.
+        let derived_key = [MAX_PRIME; BLOCK_SIZE];
+        let mut sum1 = 0i64;
+        let mut sum2 = 0i64;
+        for &p in derived_key.iter() {
+            sum1 += p as i64 * p as i64;
+            sum2 += p as i64 * (127 - -128);
+        }
+        sum2 <<= 1;
+        let element = -128 * sum1 - MAX_PRIME as i64 * sum2;
+        assert!(element.unsigned_abs() <= max_element_magnitude(BLOCK_SIZE));
+        assert!(element.unsigned_abs() > max_element_magnitude(BLOCK_SIZE) / 2);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn decrypt_block_rejects_elements_out_of_bounds() {
+        let params = Params {
+            block_size: BlockSize::Bytes512,
+            element_width: ElementWidth::Bytes8,
+            ..Params::new()
+        };
+        let derived_key = [MAX_PRIME; MAX_BLOCK_SIZE];
+        let mut decrypted_block = [0; MAX_BLOCK_SIZE];
+
+        let mut encrypted_block = [0; MAX_BLOCK_SIZE + 8 * MAX_BLOCK_SIZE];
+        let too_large = max_element_magnitude(MAX_BLOCK_SIZE) as i64 + 1;
+        encrypted_block[MAX_BLOCK_SIZE..MAX_BLOCK_SIZE + 8]
+            .copy_from_slice(&too_large.to_le_bytes());
+        assert_eq!(
+            decrypt_block(
+                &encrypted_block,
+                &derived_key,
+                &params,
+                &mut decrypted_block
+            ),
+            Err(Error::InvalidCiphertext)
+        );
+    }
+
+    #[test]
+    fn decryption_stays_exact_for_larger_blocks() {
+        // Leaves room for block sizes far past `MAX_BLOCK_SIZE` and elements of any width.
+        for log2_block_size in 6..=24 {
+            assert!(max_decryption_numerator(1 << log2_block_size) <= i128::MAX as u128);
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn decrypt_block_rejects_inexact_elements() {
+        let params = Params {
+            block_size: BlockSize::Bytes64,
+            element_width: ElementWidth::Bytes8,
+            ..Params::new()
+        };
+        let block = [9; 64];
+        let derived_key = [MAX_PRIME; 64];
+        let mut encrypted_block = [0; 64 + 8 * 64];
+        encrypt_block(
+            &block,
+            &derived_key,
+            &params,
+            &mut encrypted_block,
+            &mut SystemRandom::new(),
+        )
+        .unwrap();
+        let mut decrypted_block = [0; 64];
+        decrypt_block(
+            &encrypted_block,
+            &derived_key,
+            &params,
+            &mut decrypted_block,
+        )
+        .unwrap();
+        assert_eq!(decrypted_block, block);
+
+        encrypted_block[64] ^= 1;
+        assert_eq!(
+            decrypt_block(
+                &encrypted_block,
+                &derived_key,
+                &params,
+                &mut decrypted_block
+            ),
+            Err(Error::InvalidCiphertext)
+        );
     }
 }
@@ -0,0 +1,242 @@
+//! The NSE block equations in arbitrary precision, without the shortcuts `encrypt_block`
+//! and `decrypt_block` take to stay within `i64`. Slow, and only used to check the fast path.
+
+use num_bigint::BigInt;
+use std::convert::TryFrom;
+
+/// Returns the dot product of the derived key and `v`.
+fn dot(derived_key: &[u16], v: impl Iterator<Item = BigInt>) -> BigInt {
+    derived_key.iter().zip(v).map(|(&p, v)| p * v).sum()
+}
+
+/// Returns `e_i = x_i Σp² - 2 p_i Σp(x - v)` for the block `x`, the derived key `p`
+/// and the IV `v`.
+pub fn encrypt_elements(block: &[i8], derived_key: &[u16], iv: &[i8]) -> Vec<BigInt> {
+    let key_norm = dot(derived_key, derived_key.iter().map(|&p| BigInt::from(p)));
+    let key_dot_difference = 2 * dot(
+        derived_key,
+        block
+            .iter()
+            .zip(iv)
+            .map(|(&x, &v)| BigInt::from(x) - BigInt::from(v)),
+    );
+    block
+        .iter()
+        .zip(derived_key)
+        .map(|(&x, &p)| x * &key_norm - p * &key_dot_difference)
+        .collect()
+}
+
+/// Returns `n / d`, or `None` unless the division is exact.
+fn divide_exactly(n: BigInt, d: &BigInt) -> Option<BigInt> {
+    if &n % d != BigInt::from(0) {
+        return None;
+    }
+    Some(n / d)
+}
+
+/// Returns `x_i = (e_i + 2 p_i Σpv - 2 p_i Σpe / Σp²) / Σp²`, dividing every element as written,
+/// or `None` unless every division is exact and every `x_i` fits a byte.
+pub fn decrypt_elements(elements: &[BigInt], derived_key: &[u16], iv: &[i8]) -> Option<Vec<i8>> {
+    let key_norm = dot(derived_key, derived_key.iter().map(|&p| BigInt::from(p)));
+    let key_dot_iv = 2 * dot(derived_key, iv.iter().map(|&v| BigInt::from(v)));
+    let key_dot_elements = 2 * dot(derived_key, elements.iter().cloned());
+
+    elements
+        .iter()
+        .zip(derived_key)
+        .map(|(e, &p)| {
+            let correction = divide_exactly(p * &key_dot_elements, &key_norm)?;
+            let x = divide_exactly(e + p * &key_dot_iv - correction, &key_norm)?;
+            i8::try_from(x).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::bytes::join_bytes;
+    use crate::blocks::bytes::split_bytes;
+    use crate::blocks::decrypt_block;
+    use crate::blocks::encrypt_block;
+    use crate::blocks::generation::PRIMES;
+    use crate::blocks::max_element_magnitude;
+    use crate::blocks::MAX_BLOCK_SIZE;
+    use crate::params::BlockSize;
+    use crate::params::ElementWidth;
+    use crate::params::Params;
+    use crate::random::ReplayRandom;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const BLOCK_SIZES: [BlockSize; 4] = [
+        BlockSize::Bytes64,
+        BlockSize::Bytes128,
+        BlockSize::Bytes256,
+        BlockSize::Bytes512,
+    ];
+
+    /// Encrypts and decrypts `count` random blocks of every size both ways and checks
+    /// that the fast path agrees with the reference.
+    fn compare_with_reference(count: usize, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for &block_size in BLOCK_SIZES.iter() {
+            let params = Params {
+                block_size,
+                element_width: ElementWidth::Bytes8,
+                ..Params::new()
+            };
+            let block_size = block_size.bytes();
+            for _ in 0..count {
+                let block: Vec<u8> = (0..block_size).map(|_| rng.gen()).collect();
+                let derived_key: Vec<u16> = (0..block_size)
+                    .map(|_| PRIMES[rng.gen::<u8>() as usize])
+                    .collect();
+                let iv: Vec<u8> = (0..block_size).map(|_| rng.gen()).collect();
+
+                let mut encrypted_block = [0; MAX_BLOCK_SIZE + 8 * MAX_BLOCK_SIZE];
+                let mut random = ReplayRandom::new(&[], &iv, &[]);
+                // The reference accepts IVs which `encrypt_block` rejects.
+                let encrypted_length = match encrypt_block(
+                    &block,
+                    &derived_key,
+                    &params,
+                    &mut encrypted_block,
+                    &mut random,
+                ) {
+                    Ok(encrypted_length) => encrypted_length,
+                    Err(_) => continue,
+                };
+                let mut elements = [0; MAX_BLOCK_SIZE];
+                let elements = &mut elements[..block_size];
+                join_bytes(&encrypted_block[block_size..encrypted_length], 8, elements);
+
+                let signed_block: Vec<i8> = block.iter().map(|&x| x as i8).collect();
+                let signed_iv: Vec<i8> = iv.iter().map(|&v| v as i8).collect();
+                let expected = encrypt_elements(&signed_block, &derived_key, &signed_iv);
+                for (e, expected) in elements.iter().zip(&expected) {
+                    assert_eq!(&BigInt::from(*e), expected);
+                }
+
+                let mut decrypted_block = [0; MAX_BLOCK_SIZE];
+                decrypt_block(
+                    &encrypted_block[..encrypted_length],
+                    &derived_key,
+                    &params,
+                    &mut decrypted_block,
+                )
+                .unwrap();
+                assert_eq!(decrypted_block[..block_size], block[..]);
+                assert_eq!(
+                    decrypt_elements(&expected, &derived_key, &signed_iv),
+                    Some(signed_block)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fast_path_matches_reference() {
+        compare_with_reference(200, 0);
+    }
+
+    /// Takes minutes; run with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn fast_path_matches_reference_on_a_million_blocks() {
+        compare_with_reference(250_000, 1);
+    }
+
+    /// Decrypts `elements` with the fast path and the reference and checks that both accept
+    /// or reject them, and decrypt them alike.
+    fn assert_paths_agree(elements: &[i64], derived_key: &[u16], iv: &[i8], params: &Params) {
+        let block_size = iv.len();
+        let encrypted_length = 9 * block_size;
+        let mut encrypted_block = [0; MAX_BLOCK_SIZE + 8 * MAX_BLOCK_SIZE];
+        for (byte, &v) in encrypted_block.iter_mut().zip(iv) {
+            *byte = v as u8;
+        }
+        split_bytes(
+            &mut elements.iter().copied(),
+            8,
+            &mut encrypted_block[block_size..encrypted_length],
+        );
+
+        let mut decrypted_block = [0; MAX_BLOCK_SIZE];
+        let fast = decrypt_block(
+            &encrypted_block[..encrypted_length],
+            derived_key,
+            params,
+            &mut decrypted_block[..block_size],
+        )
+        .ok()
+        .map(|()| {
+            decrypted_block[..block_size]
+                .iter()
+                .map(|&x| x as i8)
+                .collect()
+        });
+        let elements: Vec<BigInt> = elements.iter().map(|&e| BigInt::from(e)).collect();
+        assert_eq!(fast, decrypt_elements(&elements, derived_key, iv));
+    }
+
+    #[test]
+    fn fast_path_matches_reference_on_tampered_and_random_elements() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for &block_size in BLOCK_SIZES.iter() {
+            let params = Params {
+                block_size,
+                element_width: ElementWidth::Bytes8,
+                ..Params::new()
+            };
+            let block_size = block_size.bytes();
+            let max_magnitude = max_element_magnitude(block_size) as i64;
+            for _ in 0..50 {
+                let block: Vec<i8> = (0..block_size).map(|_| rng.gen()).collect();
+                let derived_key: Vec<u16> = (0..block_size)
+                    .map(|_| PRIMES[rng.gen::<u8>() as usize])
+                    .collect();
+                let iv: Vec<i8> = (0..block_size).map(|_| rng.gen()).collect();
+                let key_norm: i64 = derived_key.iter().map(|&p| p as i64 * p as i64).sum();
+
+                let elements: Vec<i64> = encrypt_elements(&block, &derived_key, &iv)
+                    .iter()
+                    .map(|e| i64::try_from(e).unwrap())
+                    .collect();
+                assert_paths_agree(&elements, &derived_key, &iv, &params);
+
+                // Tampered elements, within the bound `decrypt_block` checks first.
+                let i = rng.gen_range(0, block_size);
+                let p = derived_key[i] as i64;
+                for &delta in [1, p, key_norm, p * key_norm].iter() {
+                    let sign = if rng.gen() { 1 } else { -1 };
+                    let mut tampered = elements.clone();
+                    tampered[i] += sign * delta;
+                    if tampered[i].abs() <= max_magnitude {
+                        assert_paths_agree(&tampered, &derived_key, &iv, &params);
+                    }
+                }
+
+                let random: Vec<i64> = (0..block_size)
+                    .map(|_| rng.gen_range(-max_magnitude, max_magnitude + 1))
+                    .collect();
+                assert_paths_agree(&random, &derived_key, &iv, &params);
+            }
+        }
+    }
+
+    #[test]
+    fn reference_rejects_inexact_elements() {
+        let derived_key = [2, 3, 5, 7];
+        let iv = [1, -1, 0, 4];
+        let mut elements = encrypt_elements(&[5, -6, 7, 8], &derived_key, &iv);
+        assert_eq!(
+            decrypt_elements(&elements, &derived_key, &iv),
+            Some(vec![5, -6, 7, 8])
+        );
+
+        elements[0] += 1;
+        assert_eq!(decrypt_elements(&elements, &derived_key, &iv), None);
+    }
+}
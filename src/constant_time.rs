@@ -0,0 +1,123 @@
+//! Branch-free replacements for the operations on secrets whose fast versions take time that
+//! depends on their operands, used with the `constant-time` feature. Every function returns
+//! exactly what the operation it replaces does.
+//!
+//! `generate_iv` and `generate_seeded_iv` still draw a new IV for as long as the difference
+//! between the block and the IV is orthogonal to the derived key, so the number of draws,
+//! which is almost always one, leaks whether a candidate was rejected.
+
+/// Returns all ones if `a == b` and zero otherwise.
+fn eq_mask(a: u8, b: u8) -> u16 {
+    let difference = (a ^ b) as u32;
+    ((difference.wrapping_sub(1) >> 31) as u16).wrapping_neg()
+}
+
+/// Returns all ones if `x != 0` and zero otherwise.
+fn nonzero_mask(x: u128) -> u128 {
+    ((x | x.wrapping_neg()) >> 127).wrapping_neg()
+}
+
+/// Returns `table[index]` by reading every entry of the table.
+pub fn lookup(table: &[u16; 256], index: u8) -> u16 {
+    table.iter().enumerate().fold(0, |value, (i, &entry)| {
+        value | entry & eq_mask(i as u8, index)
+    })
+}
+
+pub fn is_zero(x: i64) -> bool {
+    let x = x as u64;
+    (x | x.wrapping_neg()) >> 63 == 0
+}
+
+/// Returns the quotient and remainder of the long division of `numerator` by a nonzero
+/// `denominator`, one bit at a time.
+fn div_rem(numerator: u128, denominator: u128) -> (u128, u128) {
+    let mut quotient = 0;
+    let mut remainder = 0;
+    for bit in (0..128).rev() {
+        remainder = remainder << 1 | numerator >> bit & 1;
+        let (difference, borrow) = remainder.overflowing_sub(denominator);
+        let fits = (borrow as u128).wrapping_sub(1);
+        remainder = difference & fits | remainder & !fits;
+        quotient |= (fits & 1) << bit;
+    }
+    (quotient, remainder)
+}
+
+pub fn rem(numerator: u128, denominator: u128) -> u128 {
+    div_rem(numerator, denominator).1
+}
+
+/// Returns `numerator.div_euclid(denominator)` as an `i8` for a positive `denominator`, and
+/// whether the division is exact and its quotient fits an `i8`.
+pub fn div_exact_i8(numerator: i128, denominator: i128) -> (i8, bool) {
+    let sign = (numerator >> 127) as u128;
+    let magnitude = (numerator as u128 ^ sign).wrapping_sub(sign);
+    let (quotient, remainder) = div_rem(magnitude, denominator as u128);
+
+    // Below zero, Euclidean division rounds the truncated quotient down.
+    let inexact = nonzero_mask(remainder);
+    let quotient = (quotient ^ sign)
+        .wrapping_sub(sign)
+        .wrapping_sub(sign & inexact & 1);
+    let fits = quotient.wrapping_add(128) >> 8 == 0;
+    (quotient as i8, inexact == 0 && fits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::generation::PRIMES;
+    use std::convert::TryFrom;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn lookup_matches_indexing() {
+        for i in 0..=255 {
+            assert_eq!(lookup(&PRIMES, i), PRIMES[i as usize]);
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn is_zero_matches_comparison() {
+        for &x in [0, 1, -1, 2, i64::MIN, i64::MAX, 1 << 40].iter() {
+            assert_eq!(is_zero(x), x == 0);
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn rem_matches_remainder() {
+        for &numerator in [0, 1, 4095, 4096, u128::MAX, u128::MAX / 3].iter() {
+            for &denominator in [1, 8, 4096, 8 * 1000].iter() {
+                assert_eq!(rem(numerator, denominator), numerator % denominator);
+            }
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn div_exact_i8_matches_euclidean_division() {
+        let denominators = [1, 3, 1 << 20, 1_800_000_000_000_000_007];
+        for &denominator in denominators.iter() {
+            for &quotient in [-129, -128, -5, -1, 0, 1, 77, 127, 128, 1 << 60].iter() {
+                for &offset in [-1, 0, 1].iter() {
+                    let numerator: i128 = quotient * denominator + offset;
+                    let expected = numerator.rem_euclid(denominator) == 0
+                        && i8::try_from(numerator.div_euclid(denominator)).is_ok();
+                    let (actual, exact) = div_exact_i8(numerator, denominator);
+                    assert_eq!(exact, expected, "{} / {}", numerator, denominator);
+                    if exact {
+                        assert_eq!(actual as i128, numerator.div_euclid(denominator));
+                    }
+                }
+            }
+        }
+    }
+}
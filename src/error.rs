@@ -0,0 +1,50 @@
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The caller-provided output buffer is shorter than `required` bytes.
+    OutputTooSmall { required: usize, actual: usize },
+    /// The ciphertext is truncated or its padding does not fit in it.
+    InvalidCiphertext,
+    /// The parameters cannot be used together, see `Params::is_supported`.
+    UnsupportedParams,
+    /// The padding of the data is too long to count in four bytes.
+    DataTooLong,
+    /// The random source of the platform did not provide any bytes.
+    RandomSourceFailed,
+    /// The random source of the platform failed a continuous health test,
+    /// for example by returning the same byte over and over. It lasts until
+    /// `reset_random_health_tests`.
+    RandomSourceUnhealthy,
+    /// `self_test` did not pass, see the `power-on-self-test` feature.
+    SelfTestFailed,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::OutputTooSmall { required, actual } => write!(
+                f,
+                "output buffer is {} bytes long, but {} bytes are required",
+                actual, required
+            ),
+            Error::InvalidCiphertext => write!(f, "invalid ciphertext"),
+            Error::UnsupportedParams => write!(f, "unsupported parameters"),
+            Error::DataTooLong => write!(f, "data too long"),
+            Error::RandomSourceFailed => write!(f, "random source failed"),
+            Error::RandomSourceUnhealthy => write!(f, "random source failed a health test"),
+            Error::SelfTestFailed => write!(f, "self-test failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for JsValue {
+    fn from(error: Error) -> JsValue {
+        js_sys::Error::new(&error.to_string()).into()
+    }
+}
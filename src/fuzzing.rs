@@ -0,0 +1,10 @@
+//! Internals of the block codec for the fuzz targets in `fuzz/`, which are run with
+//! `cargo +nightly fuzz run <target>` and start from the seeds in `fuzz/corpus/<target>`.
+//! Nothing here is part of the stable API.
+
+pub use crate::blocks::bytes::join_bytes;
+pub use crate::blocks::bytes::packed_len;
+pub use crate::blocks::bytes::split_bytes;
+pub use crate::blocks::bytes::unpack_bytes;
+pub use crate::blocks::decrypt_block;
+pub use crate::blocks::generation::PRIMES;
@@ -0,0 +1,129 @@
+use crate::blocks::SALT_SIZE;
+use crate::params::PaddingMode;
+use crate::params::Params;
+
+const MAGIC: [u8; 3] = *b"NSE";
+const VERSION: u8 = 2;
+
+/// Ciphertexts since version 2 start with a header:
+///
+/// | bytes  | content           |
+/// |--------|-------------------|
+/// | 0..3   | `NSE`             |
+/// | 3      | format version    |
+/// | 4      | `IvMode`          |
+/// | 5      | `ElementCodec`    |
+/// | 6      | `ElementWidth`    |
+/// | 7      | binary logarithm of `BlockSize` |
+/// | 8      | `PaddingMode`     |
+/// | 9      | `PaddingPolicy`   |
+/// | 10..14 | bucket size of `PaddingPolicy::Buckets`, big-endian |
+/// | 14..30 | salt of the message |
+/// | 30..34 | number of padding bytes, big-endian, only with `PaddingMode::Compact` |
+///
+/// Ciphertexts of the 0.1 format have no header and start directly with the salt of the first
+/// block.
+pub const HEADER_SIZE: usize = PARAMS_START + Params::ENCODED_SIZE + SALT_SIZE;
+
+const PADDING_LEN_SIZE: usize = 4;
+
+/// Returns the size of the header of a ciphertext with `params`, which is at least
+/// `HEADER_SIZE`.
+pub fn header_size(params: &Params) -> usize {
+    match params.padding_mode {
+        PaddingMode::Block | PaddingMode::Embedded => HEADER_SIZE,
+        PaddingMode::Compact => HEADER_SIZE + PADDING_LEN_SIZE,
+    }
+}
+
+const PARAMS_START: usize = MAGIC.len() + 1;
+const SALT_START: usize = PARAMS_START + Params::ENCODED_SIZE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub params: Params,
+    pub salt: [u8; SALT_SIZE],
+    /// Number of padding bytes with `PaddingMode::Compact`, unused otherwise.
+    pub padding_len: u32,
+}
+
+impl Header {
+    pub fn write(&self, bytes: &mut [u8]) {
+        bytes[..MAGIC.len()].copy_from_slice(&MAGIC);
+        bytes[MAGIC.len()] = VERSION;
+        self.params.write(&mut bytes[PARAMS_START..SALT_START]);
+        bytes[SALT_START..HEADER_SIZE].copy_from_slice(&self.salt);
+        if self.params.padding_mode == PaddingMode::Compact {
+            bytes[HEADER_SIZE..HEADER_SIZE + PADDING_LEN_SIZE]
+                .copy_from_slice(&self.padding_len.to_be_bytes());
+        }
+    }
+
+    /// Returns `None` when `bytes` does not start with a header of a supported version.
+    pub fn read(bytes: &[u8]) -> Option<Header> {
+        if bytes.len() < HEADER_SIZE
+            || bytes[..MAGIC.len()] != MAGIC
+            || bytes[MAGIC.len()] != VERSION
+        {
+            return None;
+        }
+
+        let params = Params::read(&bytes[PARAMS_START..SALT_START])?;
+        let mut salt = [0; SALT_SIZE];
+        salt.copy_from_slice(&bytes[SALT_START..HEADER_SIZE]);
+        let mut padding_len = 0;
+        if params.padding_mode == PaddingMode::Compact {
+            let mut padding_len_bytes = [0; PADDING_LEN_SIZE];
+            padding_len_bytes
+                .copy_from_slice(bytes.get(HEADER_SIZE..HEADER_SIZE + PADDING_LEN_SIZE)?);
+            padding_len = u32::from_be_bytes(padding_len_bytes);
+        }
+        Some(Header {
+            params,
+            salt,
+            padding_len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_can_be_read_back() {
+        let header = Header {
+            params: Params {
+                padding_mode: PaddingMode::Compact,
+                ..Params::new()
+            },
+            salt: [9; SALT_SIZE],
+            padding_len: 70000,
+        };
+        let mut bytes = [0; HEADER_SIZE + PADDING_LEN_SIZE];
+        header.write(&mut bytes);
+
+        assert_eq!(header_size(&header.params), bytes.len());
+        assert_eq!(Header::read(&bytes), Some(header));
+        assert_eq!(Header::read(&bytes[..HEADER_SIZE]), None);
+    }
+
+    #[test]
+    fn read_rejects_other_versions_unknown_params_and_short_input() {
+        let mut bytes = [0; HEADER_SIZE];
+        Header {
+            params: Params::legacy(),
+            salt: [9; SALT_SIZE],
+            padding_len: 0,
+        }
+        .write(&mut bytes);
+        assert!(Header::read(&bytes).is_some());
+
+        assert_eq!(Header::read(&bytes[..HEADER_SIZE - 1]), None);
+        bytes[PARAMS_START] = 255;
+        assert_eq!(Header::read(&bytes), None);
+        bytes[PARAMS_START] = 0;
+        bytes[3] = 1;
+        assert_eq!(Header::read(&bytes), None);
+    }
+}
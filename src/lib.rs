@@ -1,102 +1,395 @@
-use crate::bit_cycling::cycle_left;
-use crate::bit_cycling::cycle_right;
+use crate::bit_cycling::rotate_bits_left;
+use crate::bit_cycling::rotate_bits_right;
 use crate::blocks::decrypt_block;
+use crate::blocks::decrypt_legacy_block;
 use crate::blocks::encrypt_block;
+use crate::blocks::packed_size;
+use crate::blocks::BlockKeys;
 use crate::blocks::BLOCK_SIZE;
-use crate::blocks::ENCRYPTED_BLOCK_SIZE;
-use js_sys::Uint8Array;
-use num_bigint::BigUint;
+use crate::blocks::IV_SEED_SIZE;
+use crate::blocks::LEGACY_ENCRYPTED_BLOCK_SIZE;
+use crate::blocks::MAX_BLOCK_SIZE;
+use crate::blocks::MIN_PACKED_WIDTH;
+use crate::blocks::SALT_SIZE;
+pub use crate::error::{Error, Result};
+use crate::header::header_size;
+use crate::header::Header;
+use crate::header::HEADER_SIZE;
+use crate::padding::padded_len;
+use crate::padding::read_padding_count;
+use crate::padding::write_padding_count;
+use crate::padding::EMBEDDED_COUNT_SIZE;
+pub use crate::params::{
+    BlockSize, ElementCodec, ElementWidth, IvMode, PaddingMode, PaddingPolicy, Params,
+};
+use crate::random::RandomSource;
+use crate::random::SystemRandom;
+pub use crate::security::{estimate_security, SecurityReport};
+pub use crate::self_test::{self_test, SelfTestReport};
 use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
 use wasm_bindgen::prelude::wasm_bindgen;
 
+#[cfg(feature = "analysis")]
+pub mod analysis;
 mod bit_cycling;
 mod blocks;
+#[cfg(any(feature = "constant-time", test))]
+mod constant_time;
+mod error;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+mod header;
+mod padding;
+mod params;
+mod random;
+#[cfg(feature = "research")]
+pub mod research;
+mod security;
+mod self_test;
+#[cfg(test)]
 mod tests;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod timing;
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = ["window", "crypto"])]
-    fn getRandomValues(typedArray: Uint8Array) -> Uint8Array;
+/// Strips leading zero bytes, so that keys which used to be read as the same
+/// big-endian number stay interchangeable.
+fn normalize_key(key_bytes: &[u8]) -> &[u8] {
+    match key_bytes.iter().position(|&b| b != 0) {
+        Some(start) => &key_bytes[start..],
+        None => &[0],
+    }
 }
 
-fn hash_number(number: &BigUint) -> BigUint {
+fn hash_number(key: &[u8]) -> [u8; 32] {
     let mut hasher = Sha256::default();
-    hasher.update(number.to_bytes_be());
+    hasher.update(key);
+
+    hasher.finalize().into()
+}
+
+/// Reduces the big-endian number stored in `hash` modulo the bit length of the data.
+fn shift_amount(hash: &[u8], data_len: usize) -> usize {
+    let modulus = 8 * data_len as u128;
+    #[cfg(not(feature = "constant-time"))]
+    let rem = |x: u128| x % modulus;
+    #[cfg(feature = "constant-time")]
+    let rem = |x: u128| crate::constant_time::rem(x, modulus);
+    hash.iter()
+        .fold(0u128, |acc, &b| rem((acc << 8) | b as u128)) as usize
+}
+
+/// Returns the length of the ciphertext produced for `plain_len` bytes of data
+/// with the default parameters.
+#[wasm_bindgen]
+pub fn encrypted_len(plain_len: usize) -> Result<usize> {
+    Params::default().encrypted_len(plain_len)
+}
 
-    let output = hasher.finalize();
+/// Returns an output buffer length sufficient for `decrypt_into` for `cipher_len` bytes of
+/// ciphertext produced by `encrypt` with any parameters. The plaintext is always shorter,
+/// since the padding is decrypted into the same buffer.
+///
+/// The bound assumes the smallest blocks `encrypt` writes, seeded and packed with
+/// `MIN_PACKED_WIDTH` bits. Prefer `decrypted_buffer_len`, which reads the ciphertext and
+/// returns the exact length.
+#[wasm_bindgen]
+pub fn max_decrypted_len(cipher_len: usize) -> usize {
+    let legacy_blocks = cipher_len / LEGACY_ENCRYPTED_BLOCK_SIZE;
+    [
+        BlockSize::Bytes64,
+        BlockSize::Bytes128,
+        BlockSize::Bytes256,
+        BlockSize::Bytes512,
+    ]
+    .iter()
+    .map(|block_size| {
+        let block_size = block_size.bytes();
+        let smallest_encrypted_block_size =
+            IV_SEED_SIZE + packed_size(MIN_PACKED_WIDTH, block_size);
+        let blocks = cipher_len.saturating_sub(HEADER_SIZE) / smallest_encrypted_block_size;
+        blocks.saturating_mul(block_size)
+    })
+    .fold(legacy_blocks * BLOCK_SIZE, usize::max)
+}
 
-    return BigUint::from_bytes_be(&output);
+#[wasm_bindgen]
+pub fn encrypt(data: &[u8], key_bytes: &[u8]) -> Result<Vec<u8>> {
+    Params::default().encrypt(data, key_bytes)
 }
 
+/// Encrypts `data` with the default parameters into the beginning of `out`
+/// and returns the number of bytes written.
 #[wasm_bindgen]
-pub fn encrypt(data: Uint8Array, key_bytes: Uint8Array) -> Uint8Array {
-    let key = &BigUint::from_bytes_be(&key_bytes.to_vec());
-    let bytes_to_pad = (BLOCK_SIZE - (data.length() as usize % BLOCK_SIZE)) % BLOCK_SIZE;
-    let mut padded_data = vec![0; data.length() as usize + BLOCK_SIZE + bytes_to_pad];
+pub fn encrypt_into(data: &[u8], key_bytes: &[u8], out: &mut [u8]) -> Result<usize> {
+    Params::default().encrypt_into(data, key_bytes, out)
+}
 
-    for i in 0..data.length() {
-        padded_data[i as usize] = data.get_index(i);
+/// Lets encryption use the platform's random source again after its health tests failed,
+/// which otherwise fails every encryption until the process restarts. The tests start over
+/// with no history, so only call it once the source has been fixed.
+#[wasm_bindgen]
+pub fn reset_random_health_tests() {
+    random::reset_system_health_tests()
+}
+
+#[wasm_bindgen]
+impl Params {
+    /// Returns the length of the ciphertext produced for `plain_len` bytes of data,
+    /// or its upper bound for `ElementCodec::Packed`. Fails with `Error::DataTooLong` if it
+    /// does not fit a `usize`.
+    pub fn encrypted_len(&self, plain_len: usize) -> Result<usize> {
+        let blocks = padded_len(self, plain_len)? / self.block_size.bytes();
+        blocks
+            .checked_mul(self.encrypted_block_size())
+            .and_then(|len| len.checked_add(header_size(self)))
+            .ok_or(Error::DataTooLong)
     }
 
-    let padding_bytes = getRandomValues(Uint8Array::new_with_length(
-        padded_data.len() as u32 - data.length(),
-    ));
+    pub fn encrypt(&self, data: &[u8], key_bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut encrypted_data = vec![0; self.encrypted_len(data.len())?];
+        let encrypted_length = self.encrypt_into(data, key_bytes, &mut encrypted_data)?;
+        encrypted_data.truncate(encrypted_length);
+        Ok(encrypted_data)
+    }
 
-    for i in 0..padding_bytes.length() {
-        padded_data[i as usize + data.length() as usize] = padding_bytes.get_index(i);
+    /// Encrypts `data` into the beginning of `out` and returns the number of bytes written.
+    pub fn encrypt_into(&self, data: &[u8], key_bytes: &[u8], out: &mut [u8]) -> Result<usize> {
+        #[cfg(feature = "power-on-self-test")]
+        self_test::check_on_first_use()?;
+        encrypt_into_with_random(self, data, key_bytes, out, &mut SystemRandom::new())
     }
+}
 
-    let last_index = padded_data.len() - 1;
-    padded_data[last_index] = bytes_to_pad as u8;
-    let bits_to_shift = hash_number(key);
-    let mut cycled_data = Vec::with_capacity(padded_data.len() as usize);
+/// Like `Params::encrypt_into`, but draws the salt, IVs and padding from `random`.
+fn encrypt_into_with_random<R: RandomSource>(
+    params: &Params,
+    data: &[u8],
+    key_bytes: &[u8],
+    out: &mut [u8],
+    random: &mut R,
+) -> Result<usize> {
+    if !params.is_supported() {
+        return Err(Error::UnsupportedParams);
+    }
+    let encrypted_len = params.encrypted_len(data.len())?;
+    if out.len() < encrypted_len {
+        return Err(Error::OutputTooSmall {
+            required: encrypted_len,
+            actual: out.len(),
+        });
+    }
 
-    cycle_right(&padded_data, &bits_to_shift, &mut cycled_data);
+    let block_size = params.block_size.bytes();
+    let padded_len = padded_len(params, data.len())?;
 
-    let result = Uint8Array::new_with_length(
-        (cycled_data.len() as u32 / BLOCK_SIZE as u32) * ENCRYPTED_BLOCK_SIZE as u32,
-    );
+    let padding_len = u32::try_from(padded_len - data.len()).map_err(|_| Error::DataTooLong)?;
 
-    for i in (0..padded_data.len()).step_by(BLOCK_SIZE) {
-        let encrypted_block_start = i as u32 / BLOCK_SIZE as u32 * ENCRYPTED_BLOCK_SIZE as u32;
-        encrypt_block(
-            &cycled_data[i..i + BLOCK_SIZE],
-            key,
-            &result.subarray(
-                encrypted_block_start,
-                encrypted_block_start + ENCRYPTED_BLOCK_SIZE as u32,
-            ),
-        );
+    let key = normalize_key(key_bytes);
+    let mut header = Header {
+        params: *params,
+        salt: [0; SALT_SIZE],
+        padding_len,
+    };
+    random.fill_salt(&mut header.salt)?;
+    let header_size = header_size(params);
+    header.write(&mut out[..header_size]);
+    let block_keys = BlockKeys::new(key, &header.salt);
+
+    // The padded data is staged at the end of the output, where every block is read
+    // before the encrypted blocks written in front of it can reach it.
+    let padded_start = encrypted_len - padded_len;
+    let padded_data = &mut out[padded_start..encrypted_len];
+    padded_data[..data.len()].copy_from_slice(data);
+
+    random.fill_padding(&mut padded_data[data.len()..])?;
+
+    match params.padding_mode {
+        PaddingMode::Block => write_padding_count(
+            padded_data,
+            params.block_size.padding_count_size(),
+            padding_len - block_size as u32,
+        ),
+        PaddingMode::Compact => {}
+        PaddingMode::Embedded => write_padding_count(padded_data, EMBEDDED_COUNT_SIZE, padding_len),
     }
-    return result;
+    let bits_to_shift = shift_amount(&hash_number(key), padded_len);
+
+    rotate_bits_right(padded_data, bits_to_shift);
+
+    let encrypted_block_size = params.encrypted_block_size();
+    let mut encrypted_position = header_size;
+    let mut block = [0; MAX_BLOCK_SIZE];
+    let block = &mut block[..block_size];
+    let mut derived_key = [0; MAX_BLOCK_SIZE];
+    let derived_key = &mut derived_key[..block_size];
+    for i in 0..padded_len / block_size {
+        let block_start = padded_start + i * block_size;
+        block.copy_from_slice(&out[block_start..block_start + block_size]);
+        block_keys.derive(i as u64, derived_key);
+
+        encrypted_position += encrypt_block(
+            block,
+            derived_key,
+            params,
+            &mut out[encrypted_position..encrypted_position + encrypted_block_size],
+            random,
+        )?;
+    }
+    Ok(encrypted_position)
 }
 
-#[wasm_bindgen]
-pub fn decrypt(encrypted_data: Uint8Array, key_bytes: Uint8Array) -> Uint8Array {
-    let key = &BigUint::from_bytes_be(&key_bytes.to_vec());
-    let mut cycled_data =
-        vec![0; encrypted_data.length() as usize / ENCRYPTED_BLOCK_SIZE * BLOCK_SIZE];
-    for i in (0..encrypted_data.length()).step_by(ENCRYPTED_BLOCK_SIZE) {
-        let decrypted_block_start = i as usize / ENCRYPTED_BLOCK_SIZE * BLOCK_SIZE;
-        decrypt_block(
-            &encrypted_data.subarray(i, i + ENCRYPTED_BLOCK_SIZE as u32),
-            key,
-            &mut cycled_data[decrypted_block_start..decrypted_block_start + BLOCK_SIZE],
-        );
+/// The encrypted blocks of a ciphertext.
+struct Blocks<'a> {
+    /// `None` for the 0.1 format.
+    header: Option<Header>,
+    bytes: &'a [u8],
+    count: usize,
+}
+
+impl Blocks<'_> {
+    fn block_size(&self) -> usize {
+        match &self.header {
+            Some(header) => header.params.block_size.bytes(),
+            None => BLOCK_SIZE,
+        }
+    }
+
+    /// Returns the number of padding bytes at the end of the decrypted `padded_data`.
+    fn padding_len(&self, padded_data: &[u8]) -> usize {
+        match &self.header {
+            Some(header) => match header.params.padding_mode {
+                PaddingMode::Block => {
+                    let count_size = header.params.block_size.padding_count_size();
+                    read_padding_count(padded_data, count_size) + self.block_size()
+                }
+                PaddingMode::Compact => header.padding_len as usize,
+                PaddingMode::Embedded => read_padding_count(padded_data, EMBEDDED_COUNT_SIZE),
+            },
+            None => read_padding_count(padded_data, 1) + BLOCK_SIZE,
+        }
+    }
+
+    fn parse(encrypted_data: &[u8]) -> Result<Blocks<'_>> {
+        if let Some(header) = Header::read(encrypted_data) {
+            let mut blocks = Blocks {
+                bytes: &encrypted_data[header_size(&header.params)..],
+                header: Some(header),
+                count: 0,
+            };
+            if let Some(count) = blocks.count_blocks() {
+                blocks.count = count;
+                return Ok(blocks);
+            }
+        }
+
+        let mut blocks = Blocks {
+            header: None,
+            bytes: encrypted_data,
+            count: 0,
+        };
+        blocks.count = blocks.count_blocks().ok_or(Error::InvalidCiphertext)?;
+        Ok(blocks)
+    }
+
+    fn block_len(&self, bytes: &[u8]) -> Option<usize> {
+        match &self.header {
+            Some(header) => header.params.encrypted_block_len(bytes),
+            None if bytes.len() >= LEGACY_ENCRYPTED_BLOCK_SIZE => Some(LEGACY_ENCRYPTED_BLOCK_SIZE),
+            None => None,
+        }
+    }
+
+    /// Returns `None` unless the bytes are split exactly into at least one block.
+    fn count_blocks(&self) -> Option<usize> {
+        let mut count = 0;
+        let mut position = 0;
+        while position < self.bytes.len() {
+            position += self.block_len(&self.bytes[position..])?;
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some(count)
     }
+}
 
-    let bits_to_shift = hash_number(key);
-    let mut padded_data = Vec::with_capacity(cycled_data.len());
-    cycle_left(&cycled_data, &bits_to_shift, &mut padded_data);
-    let last_index = padded_data.len() - 1;
+/// Returns the exact output buffer length `decrypt_into` needs for `encrypted_data`.
+#[wasm_bindgen]
+pub fn decrypted_buffer_len(encrypted_data: &[u8]) -> Result<usize> {
+    let blocks = Blocks::parse(encrypted_data)?;
+    Ok(blocks.count * blocks.block_size())
+}
 
-    let data = Uint8Array::new_with_length(
-        padded_data.len() as u32 - padded_data[last_index] as u32 - BLOCK_SIZE as u32,
-    );
+#[wasm_bindgen]
+pub fn decrypt(encrypted_data: &[u8], key_bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut data = vec![0; decrypted_buffer_len(encrypted_data)?];
+    let data_length = decrypt_into(encrypted_data, key_bytes, &mut data)?;
+    data.truncate(data_length);
+    Ok(data)
+}
 
-    for i in 0..data.length() {
-        data.set_index(i, padded_data[i as usize]);
+/// Decrypts `encrypted_data` into the beginning of `out` and returns the length of the data.
+/// The contents of `out` past that length are unspecified.
+///
+/// Ciphertexts of the 0.1 format, which carry a salt in every block instead of a header,
+/// are accepted as well.
+#[wasm_bindgen]
+pub fn decrypt_into(encrypted_data: &[u8], key_bytes: &[u8], out: &mut [u8]) -> Result<usize> {
+    #[cfg(feature = "power-on-self-test")]
+    self_test::check_on_first_use()?;
+    decrypt_into_without_self_test(encrypted_data, key_bytes, out)
+}
+
+fn decrypt_into_without_self_test(
+    encrypted_data: &[u8],
+    key_bytes: &[u8],
+    out: &mut [u8],
+) -> Result<usize> {
+    let blocks = Blocks::parse(encrypted_data)?;
+    let block_size = blocks.block_size();
+    let padded_len = blocks.count * block_size;
+    if out.len() < padded_len {
+        return Err(Error::OutputTooSmall {
+            required: padded_len,
+            actual: out.len(),
+        });
+    }
+
+    let key = normalize_key(key_bytes);
+    let padded_data = &mut out[..padded_len];
+    let block_keys = blocks
+        .header
+        .as_ref()
+        .map(|header| (header.params, BlockKeys::new(key, &header.salt)));
+    let mut derived_key = [0; MAX_BLOCK_SIZE];
+    let derived_key = &mut derived_key[..block_size];
+    let mut encrypted_position = 0;
+    for i in 0..blocks.count {
+        let encrypted_block_len = blocks
+            .block_len(&blocks.bytes[encrypted_position..])
+            .ok_or(Error::InvalidCiphertext)?;
+        let encrypted_block =
+            &blocks.bytes[encrypted_position..encrypted_position + encrypted_block_len];
+        encrypted_position += encrypted_block_len;
+        let decrypted_block = &mut padded_data[i * block_size..(i + 1) * block_size];
+
+        match &block_keys {
+            Some((params, block_keys)) => {
+                block_keys.derive(i as u64, derived_key);
+                decrypt_block(encrypted_block, derived_key, params, decrypted_block)?;
+            }
+            None => decrypt_legacy_block(encrypted_block, key, decrypted_block)?,
+        }
     }
 
-    return data;
+    let bits_to_shift = shift_amount(&hash_number(key), padded_len);
+    rotate_bits_left(padded_data, bits_to_shift);
+
+    let padding_len = blocks.padding_len(padded_data);
+    if padding_len > padded_len {
+        return Err(Error::InvalidCiphertext);
+    }
+    Ok(padded_len - padding_len)
 }
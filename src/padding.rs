@@ -0,0 +1,170 @@
+use crate::error::{Error, Result};
+use crate::params::PaddingMode;
+use crate::params::PaddingPolicy;
+use crate::params::Params;
+
+/// Number of bytes at the end of the padded data which count the padding with
+/// `PaddingMode::Embedded`.
+pub const EMBEDDED_COUNT_SIZE: usize = 4;
+
+/// Returns `n / d` rounded up, like `usize::div_ceil` of Rust 1.73.
+fn div_round_up(n: usize, d: usize) -> usize {
+    n / d + (n % d != 0) as usize
+}
+
+/// Returns the length of `plain_len` bytes of data once padded to whole blocks
+/// according to the padding policy, or `Error::DataTooLong` if it does not fit a `usize`.
+pub fn padded_len(params: &Params, plain_len: usize) -> Result<usize> {
+    let block_size = params.block_size.bytes();
+    let blocks = match params.padding_mode {
+        PaddingMode::Block => div_round_up(plain_len, block_size) + 1,
+        PaddingMode::Compact => div_round_up(plain_len, block_size).max(1),
+        PaddingMode::Embedded => {
+            let policy_len = plain_len
+                .checked_add(EMBEDDED_COUNT_SIZE)
+                .and_then(|counted_len| match params.padding_policy {
+                    PaddingPolicy::WholeBlocks => Some(counted_len),
+                    PaddingPolicy::PowerOfTwo => counted_len.checked_next_power_of_two(),
+                    PaddingPolicy::Padme => padme_len(counted_len),
+                    PaddingPolicy::Buckets => {
+                        let bucket_size = params.bucket_size as usize;
+                        div_round_up(counted_len, bucket_size).checked_mul(bucket_size)
+                    }
+                })
+                .ok_or(Error::DataTooLong)?;
+            div_round_up(policy_len, block_size)
+        }
+    };
+    blocks.checked_mul(block_size).ok_or(Error::DataTooLong)
+}
+
+/// Rounds `len` up so that only the `floor(log2(floor(log2(len)))) + 1` most significant bits
+/// of the result can be set, as described in "Reducing Metadata Leakage from Encrypted Files
+/// and Communication with PURBs".
+/// Returns `None` if the result does not fit a `usize`.
+fn padme_len(len: usize) -> Option<usize> {
+    if len < 2 {
+        return Some(len);
+    }
+    let exponent = usize::BITS - 1 - len.leading_zeros();
+    let significant_bits = u32::BITS - exponent.leading_zeros();
+    let mask = (1 << (exponent - significant_bits)) - 1;
+    Some(len.checked_add(mask)? & !mask)
+}
+
+/// Stores the number of padding bytes big-endian in the last `count_size` bytes of the padded
+/// data, which have to hold it. `PaddingMode::Block` leaves the extra block out of the count.
+pub fn write_padding_count(padded_data: &mut [u8], count_size: usize, bytes_to_pad: u32) {
+    let padded_len = padded_data.len();
+    let count = bytes_to_pad.to_be_bytes();
+    padded_data[padded_len - count_size..].copy_from_slice(&count[count.len() - count_size..]);
+}
+
+pub fn read_padding_count(padded_data: &[u8], count_size: usize) -> usize {
+    padded_data[padded_data.len() - count_size..]
+        .iter()
+        .fold(0, |count, &b| (count << 8) | b as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::BlockSize;
+
+    #[test]
+    fn compact_padding_fills_only_the_last_block() {
+        let mut params = Params::new();
+        params.padding_mode = PaddingMode::Compact;
+        assert_eq!(padded_len(&params, 0), Ok(256));
+        assert_eq!(padded_len(&params, 1), Ok(256));
+        assert_eq!(padded_len(&params, 256), Ok(256));
+        assert_eq!(padded_len(&params, 257), Ok(512));
+
+        params.padding_mode = PaddingMode::Block;
+        assert_eq!(padded_len(&params, 0), Ok(256));
+        assert_eq!(padded_len(&params, 1), Ok(512));
+        assert_eq!(padded_len(&params, 256), Ok(512));
+
+        params.block_size = BlockSize::Bytes64;
+        assert_eq!(padded_len(&params, 65), Ok(192));
+
+        params.padding_mode = PaddingMode::Embedded;
+        assert_eq!(padded_len(&params, 0), Ok(64));
+        assert_eq!(padded_len(&params, 60), Ok(64));
+        assert_eq!(padded_len(&params, 61), Ok(128));
+    }
+
+    #[test]
+    fn padme_len_keeps_only_the_significant_bits() {
+        assert_eq!(padme_len(0), Some(0));
+        assert_eq!(padme_len(1), Some(1));
+        assert_eq!(padme_len(9), Some(10));
+        assert_eq!(padme_len(100), Some(104));
+        assert_eq!(padme_len(1000), Some(1024));
+        assert_eq!(padme_len(1025), Some(1088));
+        assert_eq!(padme_len(1_000_000), Some(1_015_808));
+    }
+
+    #[test]
+    fn padding_policies_pad_beyond_the_last_block() {
+        let mut params = Params::new();
+        params.padding_policy = PaddingPolicy::PowerOfTwo;
+        assert_eq!(padded_len(&params, 0), Ok(256));
+        assert_eq!(padded_len(&params, 300), Ok(512));
+        assert_eq!(padded_len(&params, 1500), Ok(2048));
+        assert_eq!(padded_len(&params, 2045), Ok(4096));
+
+        params.padding_policy = PaddingPolicy::Padme;
+        assert_eq!(padded_len(&params, 1000), Ok(1024));
+        assert_eq!(padded_len(&params, 1_000_000), Ok(1_015_808));
+
+        // The policies apply to the data and the count of the padding.
+        params.padding_policy = PaddingPolicy::Buckets;
+        params.bucket_size = 4096;
+        assert_eq!(padded_len(&params, 1), Ok(4096));
+        assert_eq!(padded_len(&params, 4092), Ok(4096));
+        assert_eq!(padded_len(&params, 4093), Ok(8192));
+    }
+
+    #[test]
+    fn padded_len_past_usize_is_too_long() {
+        let mut params = Params::new();
+        for &policy in [
+            PaddingPolicy::WholeBlocks,
+            PaddingPolicy::PowerOfTwo,
+            PaddingPolicy::Padme,
+            PaddingPolicy::Buckets,
+        ]
+        .iter()
+        {
+            params.padding_policy = policy;
+            params.bucket_size = 4096;
+            assert_eq!(padded_len(&params, usize::MAX - 3), Err(Error::DataTooLong));
+            assert_eq!(padded_len(&params, usize::MAX - 4), Err(Error::DataTooLong));
+        }
+        params.padding_policy = PaddingPolicy::PowerOfTwo;
+        assert_eq!(
+            padded_len(&params, usize::MAX / 2 + 1),
+            Err(Error::DataTooLong)
+        );
+
+        params.padding_mode = PaddingMode::Block;
+        params.padding_policy = PaddingPolicy::WholeBlocks;
+        assert_eq!(padded_len(&params, usize::MAX), Err(Error::DataTooLong));
+    }
+
+    #[test]
+    fn padding_count_can_be_read_back() {
+        let mut padded_data = [0; 512];
+        write_padding_count(&mut padded_data, 1, 255);
+        assert_eq!(read_padding_count(&padded_data, 1), 255);
+        assert_eq!(padded_data[510], 0);
+
+        write_padding_count(&mut padded_data, 2, 511);
+        assert_eq!(padded_data[510..], [1, 255]);
+        assert_eq!(read_padding_count(&padded_data, 2), 511);
+
+        write_padding_count(&mut padded_data, 4, 70000);
+        assert_eq!(read_padding_count(&padded_data, 4), 70000);
+    }
+}
@@ -0,0 +1,434 @@
+use crate::blocks::max_element_magnitude;
+use crate::blocks::max_packed_width;
+use crate::blocks::packed_len;
+use crate::blocks::packed_size;
+use crate::blocks::IV_SEED_SIZE;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// How the IV of every block is stored in the ciphertext.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IvMode {
+    /// The whole IV, as long as the block.
+    Full = 0,
+    /// A short seed, which is expanded into the IV on decryption.
+    Seeded = 1,
+}
+
+impl IvMode {
+    fn from_u8(value: u8) -> Option<IvMode> {
+        match value {
+            0 => Some(IvMode::Full),
+            1 => Some(IvMode::Seeded),
+            _ => None,
+        }
+    }
+}
+
+/// How the elements of every encrypted block are stored.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementCodec {
+    /// Every element takes `ElementWidth` bytes in two's complement.
+    Fixed = 0,
+    /// Zigzag-encoded elements packed with as many bits as the widest element
+    /// of the block needs.
+    Packed = 1,
+}
+
+impl ElementCodec {
+    fn from_u8(value: u8) -> Option<ElementCodec> {
+        match value {
+            0 => Some(ElementCodec::Fixed),
+            1 => Some(ElementCodec::Packed),
+            _ => None,
+        }
+    }
+}
+
+/// Number of bytes every element takes with `ElementCodec::Fixed`. How wide the elements of a
+/// block can get depends on `BlockSize`, see `max_element_magnitude`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementWidth {
+    Bytes5 = 5,
+    Bytes6 = 6,
+    Bytes8 = 8,
+}
+
+impl ElementWidth {
+    fn from_u8(value: u8) -> Option<ElementWidth> {
+        match value {
+            5 => Some(ElementWidth::Bytes5),
+            6 => Some(ElementWidth::Bytes6),
+            8 => Some(ElementWidth::Bytes8),
+            _ => None,
+        }
+    }
+
+    pub const fn bytes(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the largest magnitude this width stores in two's complement.
+    pub const fn max_magnitude(self) -> u64 {
+        (1 << (8 * self.bytes() - 1)) - 1
+    }
+}
+
+/// Number of bytes of data in every block.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockSize {
+    Bytes64 = 64,
+    Bytes128 = 128,
+    Bytes256 = 256,
+    Bytes512 = 512,
+}
+
+impl BlockSize {
+    /// Reads the binary logarithm of the block size, which is how it is stored in the header.
+    fn from_u8(value: u8) -> Option<BlockSize> {
+        match value {
+            6 => Some(BlockSize::Bytes64),
+            7 => Some(BlockSize::Bytes128),
+            8 => Some(BlockSize::Bytes256),
+            9 => Some(BlockSize::Bytes512),
+            _ => None,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        self.bytes().trailing_zeros() as u8
+    }
+
+    pub const fn bytes(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the number of bytes at the end of the padded data which store how much padding
+    /// was added. The padding is always shorter than a block.
+    pub fn padding_count_size(self) -> usize {
+        if self.bytes() > 256 {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+/// How the data is padded to whole blocks.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaddingMode {
+    /// Random bytes up to the end of the block plus a whole extra block, with the number of
+    /// padding bytes in its last bytes, as in the 0.1 format.
+    Block = 0,
+    /// Random bytes up to the end of the block, with the number of padding bytes stored
+    /// in the header. Unlike the other modes, this reveals the exact length of the data.
+    Compact = 1,
+    /// Random bytes up to the end of the block, with the number of padding bytes in the last
+    /// `EMBEDDED_COUNT_SIZE` bytes of the padded data. A block is added only when the last
+    /// one has no room for them.
+    Embedded = 2,
+}
+
+impl PaddingMode {
+    fn from_u8(value: u8) -> Option<PaddingMode> {
+        match value {
+            0 => Some(PaddingMode::Block),
+            1 => Some(PaddingMode::Compact),
+            2 => Some(PaddingMode::Embedded),
+            _ => None,
+        }
+    }
+}
+
+/// How far beyond the end of its last block the data is padded, to hide its length.
+/// Every policy except `WholeBlocks` requires `PaddingMode::Embedded`, so that only the
+/// policy and not the padding itself is recorded in the header.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaddingPolicy {
+    /// No further than the end of the last block.
+    WholeBlocks = 0,
+    /// Up to the next power of two.
+    PowerOfTwo = 1,
+    /// Up to the next Padmé length, which leaks at most `O(log log n)` bits of the length
+    /// with at most 12% of overhead.
+    Padme = 2,
+    /// Up to the next multiple of `Params::bucket_size`.
+    Buckets = 3,
+}
+
+impl PaddingPolicy {
+    fn from_u8(value: u8) -> Option<PaddingPolicy> {
+        match value {
+            0 => Some(PaddingPolicy::WholeBlocks),
+            1 => Some(PaddingPolicy::PowerOfTwo),
+            2 => Some(PaddingPolicy::Padme),
+            3 => Some(PaddingPolicy::Buckets),
+            _ => None,
+        }
+    }
+}
+
+/// Format options of a ciphertext. They are recorded in its header, so decryption does not
+/// need them.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params {
+    pub block_size: BlockSize,
+    pub iv_mode: IvMode,
+    pub element_codec: ElementCodec,
+    pub element_width: ElementWidth,
+    pub padding_mode: PaddingMode,
+    pub padding_policy: PaddingPolicy,
+    /// Size of the buckets of `PaddingPolicy::Buckets`, a multiple of the block size.
+    pub bucket_size: u32,
+}
+
+const DEFAULT_BUCKET_SIZE: u32 = 4096;
+
+#[wasm_bindgen]
+impl Params {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Params {
+        Params {
+            block_size: BlockSize::Bytes256,
+            iv_mode: IvMode::Full,
+            element_codec: ElementCodec::Fixed,
+            element_width: ElementWidth::Bytes5,
+            padding_mode: PaddingMode::Embedded,
+            padding_policy: PaddingPolicy::WholeBlocks,
+            bucket_size: DEFAULT_BUCKET_SIZE,
+        }
+    }
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        Params::new()
+    }
+}
+
+impl Params {
+    pub const ENCODED_SIZE: usize = 10;
+
+    /// Parameters of the 0.1 format.
+    pub fn legacy() -> Params {
+        Params {
+            block_size: BlockSize::Bytes256,
+            iv_mode: IvMode::Full,
+            element_codec: ElementCodec::Fixed,
+            element_width: ElementWidth::Bytes5,
+            padding_mode: PaddingMode::Block,
+            padding_policy: PaddingPolicy::WholeBlocks,
+            bucket_size: DEFAULT_BUCKET_SIZE,
+        }
+    }
+
+    /// Returns whether the parameters can be used together: every element of a block has
+    /// to fit the element width, which only `ElementCodec::Fixed` limits, and the padding
+    /// policy has to be one `padding_mode` can record.
+    pub fn is_supported(&self) -> bool {
+        let block_size = self.block_size.bytes();
+        let elements_fit = match self.element_codec {
+            ElementCodec::Fixed => {
+                max_element_magnitude(block_size) <= self.element_width.max_magnitude()
+            }
+            ElementCodec::Packed => true,
+        };
+        let padding_fits = match (self.padding_mode, self.padding_policy) {
+            (_, PaddingPolicy::WholeBlocks) => true,
+            (PaddingMode::Embedded, PaddingPolicy::Buckets) => {
+                self.bucket_size > 0 && self.bucket_size as usize % block_size == 0
+            }
+            (PaddingMode::Embedded, _) => true,
+            (PaddingMode::Block, _) | (PaddingMode::Compact, _) => false,
+        };
+        elements_fit && padding_fits
+    }
+
+    pub fn iv_size(&self) -> usize {
+        match self.iv_mode {
+            IvMode::Full => self.block_size.bytes(),
+            IvMode::Seeded => IV_SEED_SIZE,
+        }
+    }
+
+    /// Returns the largest size of an encrypted block; the actual size is smaller
+    /// for `ElementCodec::Packed`.
+    pub fn encrypted_block_size(&self) -> usize {
+        let block_size = self.block_size.bytes();
+        self.iv_size()
+            + match self.element_codec {
+                ElementCodec::Fixed => self.element_width.bytes() * block_size,
+                ElementCodec::Packed => packed_size(max_packed_width(block_size), block_size),
+            }
+    }
+
+    /// Returns the size of the encrypted block at the beginning of `bytes`,
+    /// or `None` if it is truncated or malformed.
+    pub fn encrypted_block_len(&self, bytes: &[u8]) -> Option<usize> {
+        let iv_size = self.iv_size();
+        let elements_size = match self.element_codec {
+            ElementCodec::Fixed => self.element_width.bytes() * self.block_size.bytes(),
+            ElementCodec::Packed => packed_len(bytes.get(iv_size..)?, self.block_size.bytes())?,
+        };
+        if bytes.len() < iv_size + elements_size {
+            return None;
+        }
+        Some(iv_size + elements_size)
+    }
+
+    pub fn write(&self, bytes: &mut [u8]) {
+        bytes[0] = self.iv_mode as u8;
+        bytes[1] = self.element_codec as u8;
+        bytes[2] = self.element_width as u8;
+        bytes[3] = self.block_size.as_u8();
+        bytes[4] = self.padding_mode as u8;
+        bytes[5] = self.padding_policy as u8;
+        bytes[6..10].copy_from_slice(&self.bucket_size.to_be_bytes());
+    }
+
+    /// Returns `None` for unknown or unsupported parameters.
+    pub fn read(bytes: &[u8]) -> Option<Params> {
+        let params = Params {
+            iv_mode: IvMode::from_u8(bytes[0])?,
+            element_codec: ElementCodec::from_u8(bytes[1])?,
+            element_width: ElementWidth::from_u8(bytes[2])?,
+            block_size: BlockSize::from_u8(bytes[3])?,
+            padding_mode: PaddingMode::from_u8(bytes[4])?,
+            padding_policy: PaddingPolicy::from_u8(bytes[5])?,
+            bucket_size: u32::from_be_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]),
+        };
+        if !params.is_supported() {
+            return None;
+        }
+        Some(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::BLOCK_SIZE;
+    use crate::blocks::ELEMENT_SIZE;
+
+    #[test]
+    fn seeded_iv_shrinks_blocks() {
+        let mut params = Params::new();
+        assert_eq!(params.encrypted_block_size(), 1536);
+
+        params.iv_mode = IvMode::Seeded;
+        assert_eq!(params.encrypted_block_size(), 1296);
+    }
+
+    #[test]
+    fn element_width_sets_fixed_block_size() {
+        let mut params = Params::new();
+        params.element_width = ElementWidth::Bytes6;
+        assert_eq!(params.encrypted_block_size(), 1792);
+        params.element_width = ElementWidth::Bytes8;
+        assert_eq!(params.encrypted_block_size(), 2304);
+        assert_eq!(ElementWidth::Bytes5.max_magnitude(), (1 << 39) - 1);
+    }
+
+    #[test]
+    fn block_size_sets_block_size_and_padding_count() {
+        let mut params = Params::new();
+        params.block_size = BlockSize::Bytes64;
+        assert_eq!(params.encrypted_block_size(), 64 + 5 * 64);
+        assert_eq!(params.block_size.padding_count_size(), 1);
+
+        params.block_size = BlockSize::Bytes512;
+        params.element_width = ElementWidth::Bytes6;
+        assert_eq!(params.encrypted_block_size(), 512 + 6 * 512);
+        assert_eq!(params.block_size.padding_count_size(), 2);
+    }
+
+    #[test]
+    fn five_byte_elements_are_too_narrow_for_512_byte_blocks() {
+        let mut params = Params::new();
+        for &block_size in [BlockSize::Bytes64, BlockSize::Bytes128, BlockSize::Bytes256].iter() {
+            params.block_size = block_size;
+            assert!(params.is_supported());
+        }
+
+        params.block_size = BlockSize::Bytes512;
+        assert!(!params.is_supported());
+        params.element_width = ElementWidth::Bytes6;
+        assert!(params.is_supported());
+        params.element_width = ElementWidth::Bytes5;
+        params.element_codec = ElementCodec::Packed;
+        assert!(params.is_supported());
+    }
+
+    #[test]
+    fn encrypted_block_len_follows_element_codec() {
+        let mut params = Params::new();
+        params.iv_mode = IvMode::Seeded;
+        let mut bytes = [0; IV_SEED_SIZE + ELEMENT_SIZE * BLOCK_SIZE];
+        bytes[IV_SEED_SIZE] = 16;
+        assert_eq!(
+            params.encrypted_block_len(&bytes),
+            Some(IV_SEED_SIZE + ELEMENT_SIZE * BLOCK_SIZE)
+        );
+        assert_eq!(params.encrypted_block_len(&bytes[1..]), None);
+
+        params.element_codec = ElementCodec::Packed;
+        assert_eq!(
+            params.encrypted_block_len(&bytes),
+            Some(IV_SEED_SIZE + 1 + 2 * BLOCK_SIZE)
+        );
+        assert_eq!(params.encrypted_block_len(&bytes[..IV_SEED_SIZE]), None);
+
+        params.block_size = BlockSize::Bytes64;
+        assert_eq!(
+            params.encrypted_block_len(&bytes),
+            Some(IV_SEED_SIZE + 1 + 2 * 64)
+        );
+    }
+
+    #[test]
+    fn params_can_be_read_back() {
+        let mut params = Params::new();
+        params.iv_mode = IvMode::Seeded;
+        params.element_codec = ElementCodec::Packed;
+        params.element_width = ElementWidth::Bytes8;
+        params.block_size = BlockSize::Bytes512;
+        params.padding_policy = PaddingPolicy::Buckets;
+        params.bucket_size = 1 << 20;
+        let mut bytes = [0; Params::ENCODED_SIZE];
+        params.write(&mut bytes);
+
+        assert_eq!(bytes[3], 9);
+        assert_eq!(Params::read(&bytes), Some(params));
+        assert_eq!(Params::read(&[2, 0, 5, 8, 0, 0, 0, 0, 0, 0]), None);
+        assert_eq!(Params::read(&[0, 2, 5, 8, 0, 0, 0, 0, 0, 0]), None);
+        assert_eq!(Params::read(&[0, 0, 7, 8, 0, 0, 0, 0, 0, 0]), None);
+        assert_eq!(Params::read(&[0, 0, 5, 10, 0, 0, 0, 0, 0, 0]), None);
+        assert_eq!(Params::read(&[0, 0, 5, 9, 0, 0, 0, 0, 0, 0]), None);
+        assert_eq!(Params::read(&[0, 0, 5, 8, 3, 0, 0, 0, 0, 0]), None);
+        assert_eq!(Params::read(&[0, 0, 5, 8, 1, 4, 0, 0, 0, 0]), None);
+        assert_eq!(Params::read(&[0, 0, 5, 8, 0, 1, 0, 0, 0, 0]), None);
+        assert_eq!(Params::read(&[0, 0, 5, 8, 1, 1, 0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn buckets_have_to_hold_whole_blocks() {
+        let mut params = Params::new();
+        params.padding_policy = PaddingPolicy::Buckets;
+        assert!(params.is_supported());
+
+        params.bucket_size = 1000;
+        assert!(!params.is_supported());
+        params.bucket_size = 0;
+        assert!(!params.is_supported());
+        params.bucket_size = 512;
+        assert!(params.is_supported());
+        params.padding_mode = PaddingMode::Block;
+        assert!(!params.is_supported());
+    }
+}
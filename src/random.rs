@@ -0,0 +1,322 @@
+use crate::error::Error;
+use crate::error::Result;
+use std::sync::Mutex;
+
+/// Where the salts, IVs and padding of a ciphertext come from.
+pub trait RandomSource {
+    fn fill(&mut self, bytes: &mut [u8]) -> Result<()>;
+
+    fn fill_salt(&mut self, salt: &mut [u8]) -> Result<()> {
+        self.fill(salt)
+    }
+
+    /// Fills the IV of a block, or its seed with `IvMode::Seeded`. Called again for
+    /// every rejected IV.
+    fn fill_iv(&mut self, iv: &mut [u8]) -> Result<()> {
+        self.fill(iv)
+    }
+
+    fn fill_padding(&mut self, padding: &mut [u8]) -> Result<()> {
+        self.fill(padding)
+    }
+}
+
+/// The random source of the platform: `crypto.getRandomValues` in the browser
+/// and the operating system everywhere else. Every byte it returns goes through
+/// `HealthTests` shared by all instances for the life of the process, so that a source
+/// which repeats itself across messages is caught even if every message draws only a few
+/// bytes. Once they fail, every instance fails until `reset_system_health_tests`.
+pub struct SystemRandom {
+    source: fn(&mut [u8]) -> Result<()>,
+    health_tests: &'static Mutex<HealthTests>,
+}
+
+static SYSTEM_HEALTH_TESTS: Mutex<HealthTests> = Mutex::new(HealthTests::new());
+
+impl SystemRandom {
+    pub const fn new() -> SystemRandom {
+        SystemRandom {
+            source: platform::fill,
+            health_tests: &SYSTEM_HEALTH_TESTS,
+        }
+    }
+}
+
+impl RandomSource for SystemRandom {
+    fn fill(&mut self, bytes: &mut [u8]) -> Result<()> {
+        (self.source)(bytes)?;
+        self.health_tests
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .check(bytes)
+    }
+}
+
+/// Clears a failure of the health tests of `SystemRandom`, which otherwise lasts for the
+/// life of the process.
+pub fn reset_system_health_tests() {
+    *SYSTEM_HEALTH_TESTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = HealthTests::new();
+}
+
+/// The repetition count test fails once a byte occurs this many times in a row, which is
+/// `1 + ceil(40 / 8)` for a false positive rate of 2^-40 and 8 bits of entropy per byte.
+const REPETITION_COUNT_CUTOFF: u32 = 6;
+/// Number of bytes the adaptive proportion test looks at in a window.
+const ADAPTIVE_PROPORTION_WINDOW: u32 = 512;
+/// The adaptive proportion test fails once the first byte of a window occurs this many times
+/// in it, which is `1 + CRITBINOM(512, 2^-8, 1 - 2^-40)`.
+const ADAPTIVE_PROPORTION_CUTOFF: u32 = 19;
+
+/// The continuous health tests of NIST SP 800-90B, section 4.4, over a stream of random bytes.
+/// Once a test fails, every later check fails as well.
+pub struct HealthTests {
+    failed: bool,
+    /// The last byte and the number of times in a row it occurred.
+    repeated: Option<(u8, u32)>,
+    /// The first byte of the current window, the number of times it occurred in the window
+    /// and the number of bytes of the window seen.
+    window: Option<(u8, u32, u32)>,
+}
+
+impl HealthTests {
+    pub const fn new() -> HealthTests {
+        HealthTests {
+            failed: false,
+            repeated: None,
+            window: None,
+        }
+    }
+
+    /// Feeds `bytes` to the tests.
+    pub fn check(&mut self, bytes: &[u8]) -> Result<()> {
+        for &b in bytes {
+            if self.failed {
+                break;
+            }
+            self.repeated = match self.repeated {
+                Some((last, count)) if last == b => {
+                    self.failed |= count + 1 >= REPETITION_COUNT_CUTOFF;
+                    Some((last, count + 1))
+                }
+                _ => Some((b, 1)),
+            };
+            self.window = match self.window {
+                Some((first, count, seen)) if seen < ADAPTIVE_PROPORTION_WINDOW => {
+                    let count = count + (first == b) as u32;
+                    self.failed |= count >= ADAPTIVE_PROPORTION_CUTOFF;
+                    Some((first, count, seen + 1))
+                }
+                _ => Some((b, 1, 1)),
+            };
+        }
+        if self.failed {
+            return Err(Error::RandomSourceUnhealthy);
+        }
+        Ok(())
+    }
+}
+
+/// Replays recorded salts, IVs and padding in order, so that known-answer vectors can be
+/// reproduced byte for byte.
+pub struct ReplayRandom<'a> {
+    salt: &'a [u8],
+    ivs: &'a [u8],
+    padding: &'a [u8],
+}
+
+impl<'a> ReplayRandom<'a> {
+    pub fn new(salt: &'a [u8], ivs: &'a [u8], padding: &'a [u8]) -> ReplayRandom<'a> {
+        ReplayRandom { salt, ivs, padding }
+    }
+
+    /// Returns whether every recorded byte has been replayed.
+    pub fn is_exhausted(&self) -> bool {
+        self.salt.is_empty() && self.ivs.is_empty() && self.padding.is_empty()
+    }
+}
+
+/// Moves the first `bytes.len()` bytes of `recorded` into `bytes`.
+fn replay(recorded: &mut &[u8], bytes: &mut [u8]) -> Result<()> {
+    if recorded.len() < bytes.len() {
+        return Err(Error::RandomSourceFailed);
+    }
+    let (replayed, rest) = recorded.split_at(bytes.len());
+    bytes.copy_from_slice(replayed);
+    *recorded = rest;
+    Ok(())
+}
+
+impl RandomSource for ReplayRandom<'_> {
+    /// Nothing is drawn without saying what for.
+    fn fill(&mut self, _bytes: &mut [u8]) -> Result<()> {
+        Err(Error::RandomSourceFailed)
+    }
+
+    fn fill_salt(&mut self, salt: &mut [u8]) -> Result<()> {
+        replay(&mut self.salt, salt)
+    }
+
+    fn fill_iv(&mut self, iv: &mut [u8]) -> Result<()> {
+        replay(&mut self.ivs, iv)
+    }
+
+    fn fill_padding(&mut self, padding: &mut [u8]) -> Result<()> {
+        replay(&mut self.padding, padding)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use crate::error::{Error, Result};
+    use js_sys::Uint8Array;
+    use wasm_bindgen::prelude::wasm_bindgen;
+    use wasm_bindgen::JsValue;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = ["window", "crypto"], catch)]
+        fn getRandomValues(typedArray: &Uint8Array) -> std::result::Result<Uint8Array, JsValue>;
+    }
+
+    /// The most bytes `getRandomValues` fills at once.
+    const MAX_CHUNK_SIZE: usize = 65536;
+
+    pub fn fill(bytes: &mut [u8]) -> Result<()> {
+        for chunk in bytes.chunks_mut(MAX_CHUNK_SIZE) {
+            let array = Uint8Array::new_with_length(chunk.len() as u32);
+            getRandomValues(&array).map_err(|_| Error::RandomSourceFailed)?;
+            array.copy_to(chunk);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use crate::error::{Error, Result};
+
+    pub fn fill(bytes: &mut [u8]) -> Result<()> {
+        getrandom::getrandom(bytes).map_err(|_| Error::RandomSourceFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn system_random_fills_more_than_one_chunk() {
+        let mut bytes = vec![0; 3 * 65536 + 1];
+        SystemRandom::new().fill(&mut bytes).unwrap();
+
+        for chunk in bytes.chunks(65536) {
+            assert!(chunk.iter().any(|&b| b != 0));
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn replay_random_replays_every_kind_in_order() {
+        let mut random = ReplayRandom::new(&[1, 2], &[3, 4, 5], &[6]);
+        let mut bytes = [0; 2];
+
+        random.fill_iv(&mut bytes).unwrap();
+        assert_eq!(bytes, [3, 4]);
+        random.fill_salt(&mut bytes).unwrap();
+        assert_eq!(bytes, [1, 2]);
+        assert_eq!(random.fill_iv(&mut bytes), Err(Error::RandomSourceFailed));
+        random.fill_iv(&mut bytes[..1]).unwrap();
+        random.fill_padding(&mut bytes[..1]).unwrap();
+        assert_eq!(bytes, [6, 2]);
+        assert!(random.is_exhausted());
+        assert_eq!(random.fill(&mut bytes), Err(Error::RandomSourceFailed));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn health_tests_pass_system_random() {
+        let mut bytes = vec![0; 1 << 20];
+        SystemRandom::new().fill(&mut bytes).unwrap();
+
+        let mut tests = HealthTests::new();
+        assert_eq!(tests.check(&bytes), Ok(()));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn health_tests_reject_a_stuck_source() {
+        let mut tests = HealthTests::new();
+        assert_eq!(tests.check(&[7, 0, 0, 0, 0, 0]), Ok(()));
+        assert_eq!(tests.check(&[0]), Err(Error::RandomSourceUnhealthy));
+        // The failure sticks.
+        assert_eq!(tests.check(&[1, 2, 3]), Err(Error::RandomSourceUnhealthy));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn health_tests_reject_a_biased_source() {
+        // Never repeats a byte, but every other byte of a window is the same.
+        let mut tests = HealthTests::new();
+        let bytes: Vec<u8> = (0..2 * ADAPTIVE_PROPORTION_CUTOFF as u8)
+            .map(|i| if i % 2 == 0 { 0 } else { i })
+            .collect();
+        let (passing, failing) = bytes.split_at(bytes.len() - 2);
+        assert_eq!(tests.check(passing), Ok(()));
+        assert_eq!(tests.check(failing), Err(Error::RandomSourceUnhealthy));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn health_tests_catch_a_source_stuck_across_encryptions() {
+        // Returns the same block of bytes on every call, so every message gets the same
+        // salt, but no message draws enough of them to fail the health tests on its own.
+        fn stuck(bytes: &mut [u8]) -> Result<()> {
+            for (i, b) in bytes.iter_mut().enumerate() {
+                *b = i as u8 % 16 + 1;
+            }
+            Ok(())
+        }
+        static STUCK_HEALTH_TESTS: Mutex<HealthTests> = Mutex::new(HealthTests::new());
+
+        let mut params = crate::params::Params::new();
+        params.iv_mode = crate::params::IvMode::Seeded;
+        let data = [0; 10];
+        let mut out = vec![0; params.encrypted_len(data.len()).unwrap()];
+        let results: Vec<_> = (0..8)
+            .map(|_| {
+                let mut random = SystemRandom {
+                    source: stuck,
+                    health_tests: &STUCK_HEALTH_TESTS,
+                };
+                crate::encrypt_into_with_random(&params, &data, &[1; 32], &mut out, &mut random)
+            })
+            .collect();
+        assert!(results[0].is_ok());
+        assert_eq!(results.last(), Some(&Err(Error::RandomSourceUnhealthy)));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn adaptive_proportion_test_starts_a_new_window() {
+        let mut tests = HealthTests::new();
+        let mut bytes = vec![0; ADAPTIVE_PROPORTION_WINDOW as usize];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            // The first byte occurs once less than the cutoff in every window.
+            *b = if i % 4 == 0 && i < 4 * (ADAPTIVE_PROPORTION_CUTOFF as usize - 1) {
+                0
+            } else {
+                (i % 255 + 1) as u8
+            };
+        }
+        assert_eq!(tests.check(&bytes), Ok(()));
+        assert_eq!(tests.check(&bytes), Ok(()));
+    }
+}
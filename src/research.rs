@@ -0,0 +1,236 @@
+//! Known-plaintext recovery of the derived key of a block, to quantify the security margin of
+//! `encrypt_block` rather than guess it.
+//!
+//! Every element is `e_i = r_i * S - p_i * T` with `S = Σp²` and `T = 2Σp(r - v)`, so the
+//! elements lie in the plane spanned by the block `r` and the derived key `p`. For any two
+//! indices, `r_j * e_i - r_i * e_j = T * (r_i * p_j - r_j * p_i)`, so the gcd of these
+//! combinations is a small multiple of `T`. Reducing the elements modulo `T` then gives
+//! `S mod T`, which leaves a single integer to search for before every `p_i` follows.
+
+use crate::blocks::generation::PRIMES;
+use crate::blocks::read_encrypted_block;
+use crate::blocks::MAX_BLOCK_SIZE;
+use crate::params::Params;
+
+/// The largest factor by which the gcd of the combinations can exceed `|T|` that is searched.
+const MAX_GCD_COFACTOR: i128 = 1024;
+
+/// Outcome of `recover_derived_key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Number of pairs looked at before the key was recovered, or all of them if it was not.
+    pub pairs_used: usize,
+    pub derived_key: Option<Vec<u16>>,
+}
+
+/// Tries to recover the derived key shared by blocks and their encryptions with `params`,
+/// one pair at a time, and reports how many pairs it took.
+pub fn recover_derived_key(params: &Params, pairs: &[(&[u8], &[u8])]) -> RecoveryReport {
+    for (i, (block, encrypted_block)) in pairs.iter().enumerate() {
+        if let Some(derived_key) = recover_from_pair(params, block, encrypted_block) {
+            return RecoveryReport {
+                pairs_used: i + 1,
+                derived_key: Some(derived_key),
+            };
+        }
+    }
+    RecoveryReport {
+        pairs_used: pairs.len(),
+        derived_key: None,
+    }
+}
+
+/// Recovers the derived key of a single block and its encryption validated with
+/// `Params::encrypted_block_len`.
+pub fn recover_from_pair(
+    params: &Params,
+    block: &[u8],
+    encrypted_block: &[u8],
+) -> Option<Vec<u16>> {
+    let block_size = params.block_size.bytes();
+    let mut iv = [0; MAX_BLOCK_SIZE];
+    let iv = &mut iv[..block_size];
+    let mut elements = [0; MAX_BLOCK_SIZE];
+    let elements = &mut elements[..block_size];
+    read_encrypted_block(encrypted_block, params, iv, elements);
+
+    let r: Vec<i128> = block.iter().map(|&x| x as i8 as i128).collect();
+    let v: Vec<i128> = iv.iter().map(|&x| x as i128).collect();
+    let e: Vec<i128> = elements.iter().map(|&x| x as i128).collect();
+
+    let multiple_of_t = match r.iter().position(|&x| x != 0) {
+        Some(a) => (0..block_size).fold(0, |g, j| gcd(g, r[a] * e[j] - r[j] * e[a])),
+        // Every element is `-p_i * T`.
+        None => e.iter().fold(0, |g, &x| gcd(g, x)),
+    };
+    (1..=MAX_GCD_COFACTOR.min(multiple_of_t))
+        .filter(|cofactor| multiple_of_t % cofactor == 0)
+        .flat_map(|cofactor| vec![multiple_of_t / cofactor, -multiple_of_t / cofactor])
+        .find_map(|t| recover_with_t(&r, &v, &e, t))
+}
+
+/// Recovers the derived key assuming `T = t`, and checks it by encrypting the block again.
+fn recover_with_t(r: &[i128], v: &[i128], e: &[i128], t: i128) -> Option<Vec<u16>> {
+    // `e_i = r_i * S (mod T)` for every `i`, with `r_i` invertible for some of them.
+    let s_mod_t = r
+        .iter()
+        .zip(e)
+        .find_map(|(&r, &e)| Some(e * inverse(r, t.abs())? % t.abs()))
+        .unwrap_or(0);
+    // `p_i = c_i + r_i * m`, where `S = s_mod_t + m * T`.
+    let mut c = Vec::with_capacity(r.len());
+    for (&r, &e) in r.iter().zip(e) {
+        let numerator = r * s_mod_t - e;
+        if numerator % t != 0 {
+            return None;
+        }
+        c.push(numerator / t);
+    }
+
+    let candidates: Vec<i128> = match r.iter().position(|&x| x != 0) {
+        Some(j) => PRIMES
+            .iter()
+            .map(|&q| q as i128 - c[j])
+            .filter(|difference| difference % r[j] == 0)
+            .map(|difference| difference / r[j])
+            .collect(),
+        None => vec![0],
+    };
+    candidates.into_iter().find_map(|m| {
+        let p: Vec<i128> = c.iter().zip(r).map(|(&c, &r)| c + r * m).collect();
+        if !p.iter().all(|&p| p > 0 && PRIMES.contains(&(p as u16))) {
+            return None;
+        }
+        let s: i128 = p.iter().map(|p| p * p).sum();
+        let t: i128 = 2 * (0..p.len()).map(|i| p[i] * (r[i] - v[i])).sum::<i128>();
+        if (0..p.len()).any(|i| r[i] * s - p[i] * t != e[i]) {
+            return None;
+        }
+        Some(p.iter().map(|&p| p as u16).collect())
+    })
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Returns the inverse of `a` modulo `modulus`, if there is one.
+fn inverse(a: i128, modulus: i128) -> Option<i128> {
+    let (mut old_r, mut r) = (a.rem_euclid(modulus), modulus);
+    let (mut old_s, mut s) = (1, 0);
+    while r != 0 {
+        let quotient = old_r / r;
+        old_r -= quotient * r;
+        std::mem::swap(&mut old_r, &mut r);
+        old_s -= quotient * s;
+        std::mem::swap(&mut old_s, &mut s);
+    }
+    if old_r != 1 {
+        return None;
+    }
+    Some(old_s.rem_euclid(modulus))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::encrypt_block;
+    use crate::blocks::BlockKeys;
+    use crate::params::{BlockSize, ElementCodec, ElementWidth, IvMode};
+    use crate::random::{RandomSource, SystemRandom};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn one_known_plaintext_recovers_the_derived_key() {
+        for &block_size in [BlockSize::Bytes64, BlockSize::Bytes512].iter() {
+            for &(iv_mode, element_codec) in [
+                (IvMode::Full, ElementCodec::Fixed),
+                (IvMode::Seeded, ElementCodec::Packed),
+            ]
+            .iter()
+            {
+                let params = Params {
+                    block_size,
+                    iv_mode,
+                    element_codec,
+                    element_width: ElementWidth::Bytes8,
+                    ..Params::new()
+                };
+                let block_size = block_size.bytes();
+                let mut derived_key = [0; MAX_BLOCK_SIZE];
+                let derived_key = &mut derived_key[..block_size];
+                BlockKeys::new(b"key", &[0; 16]).derive(0, derived_key);
+                let mut block = [0; MAX_BLOCK_SIZE];
+                let block = &mut block[..block_size];
+                SystemRandom::new().fill(block).unwrap();
+
+                let mut encrypted_block = [0; 2 * MAX_BLOCK_SIZE + 8 * MAX_BLOCK_SIZE];
+                let encrypted_length = encrypt_block(
+                    block,
+                    derived_key,
+                    &params,
+                    &mut encrypted_block,
+                    &mut SystemRandom::new(),
+                )
+                .unwrap();
+
+                let pairs: [(&[u8], &[u8]); 1] = [(block, &encrypted_block[..encrypted_length])];
+                assert_eq!(
+                    recover_derived_key(&params, &pairs),
+                    RecoveryReport {
+                        pairs_used: 1,
+                        derived_key: Some(derived_key.to_vec()),
+                    }
+                );
+            }
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn a_wrong_plaintext_recovers_nothing() {
+        let params = Params {
+            block_size: BlockSize::Bytes64,
+            ..Params::new()
+        };
+        let mut derived_key = [0; 64];
+        BlockKeys::new(b"key", &[0; 16]).derive(0, &mut derived_key);
+        let block = [5; 64];
+        let mut encrypted_block = [0; 64 + 5 * 64];
+        encrypt_block(
+            &block,
+            &derived_key,
+            &params,
+            &mut encrypted_block,
+            &mut SystemRandom::new(),
+        )
+        .unwrap();
+
+        let pairs: [(&[u8], &[u8]); 2] =
+            [(&[6; 64], &encrypted_block), (&[7; 64], &encrypted_block)];
+        assert_eq!(
+            recover_derived_key(&params, &pairs),
+            RecoveryReport {
+                pairs_used: 2,
+                derived_key: None,
+            }
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn inverse_is_modular() {
+        assert_eq!(inverse(3, 7), Some(5));
+        assert_eq!(inverse(-3, 7), Some(2));
+        assert_eq!(inverse(4, 8), None);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::blocks::generation::PRIMES;
+use crate::blocks::max_element_magnitude;
+use crate::blocks::SALT_SIZE;
+use crate::params::{ElementCodec, Params};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Number of output bits of HKDF-SHA256, which caps the entropy of every derived key.
+const HKDF_BITS: f64 = 256.0;
+
+/// Concrete security-relevant numbers of a parameter set, see `estimate_security`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SecurityReport {
+    /// `log2` of the number of derived keys of a block, every element of which is one of
+    /// the distinct `PRIMES`.
+    pub derived_key_space_bits: f64,
+    /// `derived_key_space_bits` capped by the bits of the key and by the output of
+    /// HKDF-SHA256 the derived key is expanded from.
+    pub effective_derived_key_bits: f64,
+    /// Probability that two of the messages share a salt.
+    pub salt_collision_probability: f64,
+    /// Total variation distance between the primes a uniform byte of HKDF output maps to
+    /// and the uniform distribution over the distinct `PRIMES`, see `prime_mapping_bias`.
+    pub prime_mapping_bias: f64,
+    /// Largest magnitude of an element, see `max_element_magnitude`.
+    pub max_element_magnitude: u64,
+    /// Largest magnitude the element codec stores.
+    pub element_capacity: u64,
+    /// `log2(element_capacity / max_element_magnitude)`; negative for parameters which can
+    /// overflow their elements.
+    pub element_headroom_bits: f64,
+}
+
+/// Returns the total variation distance between the primes of `primes[byte % primes.len()]`
+/// for a uniform `byte` and the uniform distribution over the distinct `primes`. The modulo
+/// favours the first `256 % primes.len()` entries, and duplicates favour their prime.
+fn prime_mapping_bias(primes: &[u16]) -> f64 {
+    let mut distinct_primes = primes.to_vec();
+    distinct_primes.sort_unstable();
+    distinct_primes.dedup();
+
+    let mut counts = vec![0usize; distinct_primes.len()];
+    for byte in 0..=u8::MAX {
+        let prime = primes[byte as usize % primes.len()];
+        counts[distinct_primes.binary_search(&prime).unwrap()] += 1;
+    }
+    let uniform = 1.0 / distinct_primes.len() as f64;
+    counts
+        .iter()
+        .map(|&count| (count as f64 / 256.0 - uniform).abs())
+        .sum::<f64>()
+        / 2.0
+}
+
+/// Estimates `SecurityReport` for `params` and a number of messages encrypted with one key
+/// of `key_len` bytes.
+#[wasm_bindgen]
+pub fn estimate_security(params: &Params, key_len: usize, messages: f64) -> SecurityReport {
+    let block_size = params.block_size.bytes();
+    // `PRIMES` is sorted, so any duplicates are adjacent.
+    let mut distinct_primes = PRIMES.to_vec();
+    distinct_primes.dedup();
+    let derived_key_space_bits = block_size as f64 * (distinct_primes.len() as f64).log2();
+    let effective_derived_key_bits = derived_key_space_bits
+        .min(8.0 * key_len as f64)
+        .min(HKDF_BITS);
+
+    // The birthday bound: `1 - exp(-m (m - 1) / 2^(bits + 1))`.
+    let salt_pairs = messages * (messages - 1.0) / 2.0;
+    let salt_collision_probability = -(-salt_pairs / 2f64.powi(8 * SALT_SIZE as i32)).exp_m1();
+
+    let max_element_magnitude = max_element_magnitude(block_size);
+    let element_capacity = match params.element_codec {
+        ElementCodec::Fixed => params.element_width.max_magnitude(),
+        // Elements widen up to the 64 bits of a zigzag-encoded `i64` whatever the block size,
+        // and the sign takes one of them.
+        ElementCodec::Packed => i64::MAX as u64,
+    };
+
+    SecurityReport {
+        derived_key_space_bits,
+        effective_derived_key_bits,
+        salt_collision_probability,
+        prime_mapping_bias: prime_mapping_bias(&PRIMES),
+        max_element_magnitude,
+        element_capacity,
+        element_headroom_bits: (element_capacity as f64 / max_element_magnitude as f64).log2(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{BlockSize, ElementWidth};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn estimate_security_of_default_params() {
+        let report = estimate_security(&Params::new(), 32, 1.0);
+        assert_eq!(report.derived_key_space_bits, 2048.0);
+        assert_eq!(report.effective_derived_key_bits, 256.0);
+        assert_eq!(report.salt_collision_probability, 0.0);
+        assert_eq!(report.prime_mapping_bias, 0.0);
+        assert_eq!(report.max_element_magnitude, 428_108_983_808);
+        assert_eq!(report.element_capacity, (1 << 39) - 1);
+        assert!(report.element_headroom_bits > 0.0 && report.element_headroom_bits < 1.0);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn salts_collide_at_the_birthday_bound() {
+        let probability =
+            estimate_security(&Params::new(), 32, 2f64.powi(64)).salt_collision_probability;
+        assert!((probability - (1.0 - (-0.5f64).exp())).abs() < 1e-9);
+
+        let probability = estimate_security(&Params::new(), 32, 1e6).salt_collision_probability;
+        assert!(probability > 1e-27 && probability < 2e-27);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn unsupported_widths_have_negative_headroom() {
+        let params = Params {
+            block_size: BlockSize::Bytes512,
+            element_width: ElementWidth::Bytes5,
+            ..Params::new()
+        };
+        assert!(!params.is_supported());
+        assert!(estimate_security(&params, 32, 1.0).element_headroom_bits < 0.0);
+
+        let params = Params {
+            block_size: BlockSize::Bytes512,
+            element_codec: ElementCodec::Packed,
+            ..Params::new()
+        };
+        let report = estimate_security(&params, 32, 1.0);
+        assert_eq!(report.element_capacity, i64::MAX as u64);
+        assert!(report.element_headroom_bits > 23.0 && report.element_headroom_bits < 24.0);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn short_keys_cap_the_effective_derived_key_bits() {
+        assert_eq!(
+            estimate_security(&Params::new(), 10, 1.0).effective_derived_key_bits,
+            80.0
+        );
+        assert_eq!(
+            estimate_security(&Params::new(), 0, 1.0).effective_derived_key_bits,
+            0.0
+        );
+        assert_eq!(
+            estimate_security(&Params::new(), 64, 1.0).effective_derived_key_bits,
+            256.0
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn prime_mapping_bias_measures_the_modulo_and_duplicates() {
+        assert_eq!(prime_mapping_bias(&PRIMES), 0.0);
+        assert_eq!(prime_mapping_bias(&[2, 3, 5, 7]), 0.0);
+        // Bytes 0 and 255 both map to the first of 255 primes.
+        assert!((prime_mapping_bias(&PRIMES[..255]) - 254.0 / 65280.0).abs() < 1e-12);
+        // Three of four bytes map to 2.
+        assert_eq!(prime_mapping_bias(&[2, 2, 2, 3]), 0.25);
+    }
+}
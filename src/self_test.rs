@@ -0,0 +1,240 @@
+use crate::bit_cycling::rotate_bits_left;
+use crate::bit_cycling::rotate_bits_right;
+use crate::blocks::bytes::join_bytes;
+use crate::blocks::bytes::pack_bytes;
+use crate::blocks::bytes::packed_len;
+use crate::blocks::bytes::split_bytes;
+use crate::blocks::bytes::unpack_bytes;
+use crate::blocks::generation::derive_key;
+use crate::blocks::generation::PRIMES;
+use crate::blocks::max_element_magnitude;
+use crate::blocks::max_packed_width;
+use crate::blocks::packed_size;
+use crate::blocks::MAX_BLOCK_SIZE;
+use crate::decrypt_into_without_self_test;
+use crate::encrypt_into_with_random;
+#[cfg(feature = "power-on-self-test")]
+use crate::error::{Error, Result};
+use crate::params::{BlockSize, ElementCodec, ElementWidth, IvMode, Params};
+use crate::random::ReplayRandom;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Outcome of every check of `self_test`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// Rotating bits right and back left restores the data, and matches a known rotation.
+    pub bit_rotation: bool,
+    /// Elements of every width and block size survive `split_bytes`/`join_bytes` and
+    /// `pack_bytes`/`unpack_bytes`.
+    pub element_codec: bool,
+    /// `derive_key` maps the output of HKDF-SHA256 test case 3 of RFC 5869 to primes.
+    pub key_derivation: bool,
+    /// A fixed message encrypts to a known ciphertext and decrypts back.
+    pub known_answer: bool,
+}
+
+#[wasm_bindgen]
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.bit_rotation && self.element_codec && self.key_derivation && self.known_answer
+    }
+}
+
+/// Runs the known-answer and round-trip checks of the building blocks of the cipher.
+#[wasm_bindgen]
+pub fn self_test() -> SelfTestReport {
+    SelfTestReport {
+        bit_rotation: check_bit_rotation(),
+        element_codec: check_element_codec(),
+        key_derivation: check_key_derivation(),
+        known_answer: check_known_answer(),
+    }
+}
+
+/// Runs `self_test` on the first call and fails this and every later call if it did not pass.
+#[cfg(feature = "power-on-self-test")]
+pub fn check_on_first_use() -> Result<()> {
+    static REPORT: std::sync::OnceLock<SelfTestReport> = std::sync::OnceLock::new();
+    if REPORT.get_or_init(self_test).passed() {
+        Ok(())
+    } else {
+        Err(Error::SelfTestFailed)
+    }
+}
+
+fn check_bit_rotation() -> bool {
+    let original = [0x12, 0x34, 0x56, 0x78, 0x9a];
+    let mut data = original;
+    rotate_bits_right(&mut data, 12);
+    let rotated = data == [0x89, 0xa1, 0x23, 0x45, 0x67];
+    rotate_bits_left(&mut data, 12);
+    rotated
+        && (0..8 * original.len()).all(|shift| {
+            rotate_bits_right(&mut data, shift);
+            rotate_bits_left(&mut data, shift);
+            data == original
+        })
+}
+
+fn check_element_codec() -> bool {
+    let block_sizes = [
+        BlockSize::Bytes64,
+        BlockSize::Bytes128,
+        BlockSize::Bytes256,
+        BlockSize::Bytes512,
+    ];
+    let widths = [
+        ElementWidth::Bytes5,
+        ElementWidth::Bytes6,
+        ElementWidth::Bytes8,
+    ];
+    let mut elements = [0; MAX_BLOCK_SIZE];
+    let mut decoded = [0; MAX_BLOCK_SIZE];
+    let mut bytes = [0; 8 * MAX_BLOCK_SIZE];
+    block_sizes.iter().all(|block_size| {
+        let block_size = block_size.bytes();
+        let max_magnitude = max_element_magnitude(block_size) as i64;
+        let elements = &mut elements[..block_size];
+        let decoded = &mut decoded[..block_size];
+        for (i, e) in elements.iter_mut().enumerate() {
+            *e = match i % 4 {
+                0 => max_magnitude,
+                1 => -max_magnitude,
+                2 => -1,
+                _ => i as i64,
+            };
+        }
+
+        let fixed = widths
+            .iter()
+            .filter(|width| max_magnitude as u64 <= width.max_magnitude())
+            .all(|width| {
+                let width = width.bytes();
+                let bytes = &mut bytes[..width * block_size];
+                split_bytes(&mut elements.iter().copied(), width, bytes);
+                join_bytes(bytes, width, decoded);
+                decoded == elements
+            });
+
+        let packed_bytes = &mut bytes[..packed_size(max_packed_width(block_size), block_size)];
+        let packed = pack_bytes(elements, packed_bytes);
+        unpack_bytes(packed_bytes, decoded);
+        fixed && packed_len(packed_bytes, block_size) == Some(packed) && decoded == elements
+    })
+}
+
+fn check_key_derivation() -> bool {
+    // HKDF-SHA256 without salt and info, RFC 5869 A.3.
+    let ikm = [0x0b; 22];
+    let okm = [
+        0x8d, 0xa4, 0xe7, 0x75, 0xa5, 0x63, 0xc1, 0x8f, 0x71, 0x5f, 0x80, 0x2a, 0x06, 0x3c, 0x5a,
+        0x31, 0xb8, 0xa1, 0x1f, 0x5c, 0x5e, 0xe1, 0x87, 0x9e, 0xc3, 0x45, 0x4e, 0x5f, 0x3c, 0x73,
+        0x8d, 0x2d, 0x9d, 0x20, 0x13, 0x95, 0xfa, 0xa4, 0xb6, 0x1a, 0x96, 0xc8,
+    ];
+    let mut derived_key = [0; 64];
+    derive_key(&ikm, &[], &mut derived_key);
+    okm.iter()
+        .zip(derived_key.iter())
+        .all(|(&b, &p)| PRIMES[b as usize] == p)
+}
+
+const KNOWN_KEY: &[u8] = b"self-test key";
+const KNOWN_PLAINTEXT: &[u8] = b"NSE self-test";
+
+/// Encrypted with `known_answer_params` and the salt, seeds and padding of `check_known_answer`.
+const KNOWN_CIPHERTEXT: &str = concat!(
+    "4e53450201010506020000001000000102030405060708090a0b0c0d0e0f10111213141516171819",
+    "1a1b1c1d1e1f2490c69a87024d27160650d739aff1f2d8ac35ef1008def0c69c8f2def5095ed0225",
+    "24ec2a90d808da0259b74a112fc40b63f38214023050b575e8f21e760f252fef065302e9bd610350",
+    "53241202d171f10daf6a6b3e028db42508d0dfea60f1bed29a156ff6e8d9f00656a60eaf977e0cf3",
+    "d688871b6f9dce2cf0ca018a39afd5f33df35a69e612900304ca01f9b2901a10b5b2ac02a59f5016",
+    "50a30796f21e760f252f8ca98d00a5d48e1e50df64e9f022718d2090b21457f056a3a61f50a30796",
+    "022550f309af331558000a0264179f9605a4f479dbbb27df682f22f29479994b5f8b0827f3dae066",
+    "7ca0606271f1c17f923a6f5ad555f7c40eeb22afdc8377f508f0b883ef3d95acf242efe72adf97e9",
+    "dff0fa0f181f2f30b22a05ed579a17",
+);
+
+fn known_answer_params() -> Params {
+    Params {
+        block_size: BlockSize::Bytes64,
+        iv_mode: IvMode::Seeded,
+        element_codec: ElementCodec::Packed,
+        ..Params::new()
+    }
+}
+
+fn check_known_answer() -> bool {
+    let params = known_answer_params();
+    let salt: Vec<u8> = (0..16).collect();
+    let seeds: Vec<u8> = (16..32).collect();
+    let padding = [0xa5; 64 - KNOWN_PLAINTEXT.len()];
+    let mut random = ReplayRandom::new(&salt, &seeds, &padding);
+
+    let mut encrypted_data = match params.encrypted_len(KNOWN_PLAINTEXT.len()) {
+        Ok(encrypted_len) => vec![0; encrypted_len],
+        Err(_) => return false,
+    };
+    let encrypted_length = match encrypt_into_with_random(
+        &params,
+        KNOWN_PLAINTEXT,
+        KNOWN_KEY,
+        &mut encrypted_data,
+        &mut random,
+    ) {
+        Ok(encrypted_length) => encrypted_length,
+        Err(_) => return false,
+    };
+    let encrypted_data = &encrypted_data[..encrypted_length];
+    let hex: String = encrypted_data
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    let mut decrypted_data = [0; 64];
+    match decrypt_into_without_self_test(encrypted_data, KNOWN_KEY, &mut decrypted_data) {
+        Ok(len) => {
+            random.is_exhausted()
+                && hex == KNOWN_CIPHERTEXT
+                && &decrypted_data[..len] == KNOWN_PLAINTEXT
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn self_test_passes() {
+        let report = self_test();
+        assert_eq!(
+            report,
+            SelfTestReport {
+                bit_rotation: true,
+                element_codec: true,
+                key_derivation: true,
+                known_answer: true,
+            }
+        );
+        assert!(report.passed());
+    }
+
+    #[cfg(feature = "power-on-self-test")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn first_use_passes_the_self_test() {
+        assert_eq!(check_on_first_use(), Ok(()));
+        assert_eq!(
+            crate::decrypt(&crate::encrypt(b"", b"key").unwrap(), b"key"),
+            Ok(vec![])
+        );
+    }
+}
@@ -1,11 +1,44 @@
+use crate::blocks::BLOCK_SIZE;
+use crate::blocks::ENCRYPTED_BLOCK_SIZE;
+use crate::blocks::LEGACY_ENCRYPTED_BLOCK_SIZE;
+use crate::blocks::SALT_SIZE;
 use crate::decrypt;
+use crate::decrypt_into;
+use crate::decrypted_buffer_len;
 use crate::encrypt;
-use js_sys::Uint8Array;
+use crate::encrypt_into;
+use crate::encrypt_into_with_random;
+use crate::encrypted_len;
+use crate::hash_number;
+use crate::header::header_size;
+use crate::header::Header;
+use crate::header::HEADER_SIZE;
+use crate::max_decrypted_len;
+use crate::normalize_key;
+use crate::random::ReplayRandom;
+use crate::shift_amount;
+use crate::BlockSize;
+use crate::ElementCodec;
+use crate::ElementWidth;
+use crate::Error;
+use crate::IvMode;
+use crate::PaddingMode;
+use crate::PaddingPolicy;
+use crate::Params;
+#[cfg(not(target_arch = "wasm32"))]
+use proptest::collection::vec;
+#[cfg(not(target_arch = "wasm32"))]
+use proptest::prelude::*;
+use serde::Deserialize;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen_test::*;
 
-#[wasm_bindgen_test]
+#[cfg(target_arch = "wasm32")]
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
 fn encrypt_can_be_reversed_with_decrypt() {
-    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
     let raw_data = [
         237, 252, 84, 64, 120, 86, 39, 29, 40, 209, 77, 44, 108, 122, 150, 132, 46, 92, 98, 25,
         173, 186, 243, 142, 77, 145, 76, 71, 245, 118, 52, 172, 221, 109, 180, 222, 235, 18, 182,
@@ -83,34 +116,429 @@ fn encrypt_can_be_reversed_with_decrypt() {
         208, 236, 97, 19, 24, 220, 0, 208, 19,
     ];
 
-    let data = Uint8Array::new_with_length(raw_data.len() as u32);
+    let key_bytes = [
+        82, 230, 93, 209, 242, 227, 249, 139, 141, 51, 42, 181, 56, 142, 179, 40,
+    ];
 
-    for i in 0..raw_data.len() {
-        data.set_index(i as u32, raw_data[i]);
-    }
+    let encrypted_data = encrypt(&raw_data, &key_bytes).unwrap();
+
+    let decrypted_data = decrypt(&encrypted_data, &key_bytes).unwrap();
+
+    assert_eq!(&raw_data[..], &decrypted_data[..]);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn encrypt_into_can_be_reversed_with_decrypt_into() {
+    let raw_data = [7; 700];
+    let key_bytes = [12, 0, 255, 3];
 
-    let raw_key_bytes = [
+    let mut encrypted_data = vec![0; encrypted_len(raw_data.len()).unwrap()];
+    let encrypted_length = encrypt_into(&raw_data, &key_bytes, &mut encrypted_data).unwrap();
+    assert_eq!(encrypted_length, encrypted_data.len());
+
+    let mut decrypted_data = vec![0; max_decrypted_len(encrypted_length)];
+    let decrypted_length = decrypt_into(&encrypted_data, &key_bytes, &mut decrypted_data).unwrap();
+    assert_eq!(&raw_data[..], &decrypted_data[..decrypted_length]);
+}
+
+#[test]
+fn normalize_key_strips_leading_zeros() {
+    assert_eq!(normalize_key(&[0, 0, 12, 0, 7]), &[12, 0, 7]);
+    assert_eq!(normalize_key(&[12, 0, 7]), &[12, 0, 7]);
+    assert_eq!(normalize_key(&[0, 0, 0]), &[0]);
+    assert_eq!(normalize_key(&[]), &[0]);
+}
+
+#[test]
+fn shift_amount_reduces_hash_as_big_endian_number() {
+    let key = [
         82, 230, 93, 209, 242, 227, 249, 139, 141, 51, 42, 181, 56, 142, 179, 40,
     ];
-    let key_bytes1 = Uint8Array::new_with_length(raw_key_bytes.len() as u32);
+    let hash = hash_number(&key);
+
+    assert_eq!(shift_amount(&hash, 1280), 8958);
+    assert_eq!(shift_amount(&hash, 2304), 2814);
+    assert_eq!(shift_amount(&hash_number(normalize_key(&[])), 256), 29);
+}
+
+#[test]
+fn encrypted_len_adds_a_padding_block() {
+    let mut params = Params::new();
+    params.padding_mode = PaddingMode::Block;
+    assert_eq!(
+        params.encrypted_len(0).unwrap(),
+        HEADER_SIZE + ENCRYPTED_BLOCK_SIZE
+    );
+    assert_eq!(
+        params.encrypted_len(1).unwrap(),
+        HEADER_SIZE + 2 * ENCRYPTED_BLOCK_SIZE
+    );
+    assert_eq!(
+        params.encrypted_len(BLOCK_SIZE).unwrap(),
+        HEADER_SIZE + 2 * ENCRYPTED_BLOCK_SIZE
+    );
+    assert_eq!(
+        params.encrypted_len(BLOCK_SIZE + 1).unwrap(),
+        HEADER_SIZE + 3 * ENCRYPTED_BLOCK_SIZE
+    );
+}
+
+#[test]
+fn encrypted_len_with_compact_padding_rounds_up_to_a_block() {
+    let params = Params {
+        padding_mode: PaddingMode::Compact,
+        ..Params::new()
+    };
+    let header_size = header_size(&params);
+    assert_eq!(header_size, HEADER_SIZE + 4);
+    assert_eq!(
+        params.encrypted_len(0).unwrap(),
+        header_size + ENCRYPTED_BLOCK_SIZE
+    );
+    assert_eq!(
+        params.encrypted_len(1).unwrap(),
+        header_size + ENCRYPTED_BLOCK_SIZE
+    );
+    assert_eq!(
+        params.encrypted_len(BLOCK_SIZE).unwrap(),
+        header_size + ENCRYPTED_BLOCK_SIZE
+    );
+    assert_eq!(
+        params.encrypted_len(BLOCK_SIZE + 1).unwrap(),
+        header_size + 2 * ENCRYPTED_BLOCK_SIZE
+    );
+}
+
+#[test]
+fn encrypted_len_with_embedded_padding_makes_room_for_the_count() {
+    assert_eq!(header_size(&Params::new()), HEADER_SIZE);
+    assert_eq!(
+        encrypted_len(0).unwrap(),
+        HEADER_SIZE + ENCRYPTED_BLOCK_SIZE
+    );
+    assert_eq!(
+        encrypted_len(BLOCK_SIZE - 4).unwrap(),
+        HEADER_SIZE + ENCRYPTED_BLOCK_SIZE
+    );
+    assert_eq!(
+        encrypted_len(BLOCK_SIZE - 3).unwrap(),
+        HEADER_SIZE + 2 * ENCRYPTED_BLOCK_SIZE
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn encrypted_len_past_usize_is_too_long() {
+    assert_eq!(encrypted_len(usize::MAX), Err(Error::DataTooLong));
+    // The padded data fits, but its encrypted blocks do not.
+    assert_eq!(encrypted_len(usize::MAX / 2), Err(Error::DataTooLong));
+
+    let params = Params {
+        padding_policy: PaddingPolicy::PowerOfTwo,
+        ..Params::new()
+    };
+    assert_eq!(
+        params.encrypted_len(usize::MAX / 2 + 1),
+        Err(Error::DataTooLong)
+    );
+}
+
+#[test]
+fn embedded_padding_keeps_the_length_out_of_the_header() {
+    let short = encrypt(&[1; 10], b"key").unwrap();
+    let long = encrypt(&[1; 200], b"key").unwrap();
+    assert_eq!(short.len(), long.len());
+    assert_eq!(
+        short[..HEADER_SIZE - SALT_SIZE],
+        long[..HEADER_SIZE - SALT_SIZE]
+    );
+    assert_eq!(decrypt(&short, b"key").unwrap(), vec![1; 10]);
+    assert_eq!(decrypt(&long, b"key").unwrap(), vec![1; 200]);
+}
+
+#[test]
+fn lengths_in_the_same_bucket_are_indistinguishable() {
+    for &padding_policy in [
+        PaddingPolicy::PowerOfTwo,
+        PaddingPolicy::Padme,
+        PaddingPolicy::Buckets,
+    ]
+    .iter()
+    {
+        let params = Params {
+            padding_policy,
+            ..Params::new()
+        };
+        let short = params.encrypt(&[1; 1000], b"key").unwrap();
+        let long = params.encrypt(&[2; 1020], b"key").unwrap();
+        assert_eq!(short.len(), long.len());
+        assert_eq!(
+            short[..HEADER_SIZE - SALT_SIZE],
+            long[..HEADER_SIZE - SALT_SIZE]
+        );
+        assert_eq!(header_size(&params), HEADER_SIZE);
+        assert_eq!(decrypt(&short, b"key").unwrap(), vec![1; 1000]);
+        assert_eq!(decrypt(&long, b"key").unwrap(), vec![2; 1020]);
+    }
+}
+
+#[test]
+fn max_decrypted_len_covers_whole_blocks() {
+    assert_eq!(max_decrypted_len(0), 0);
+    assert!(max_decrypted_len(HEADER_SIZE + ENCRYPTED_BLOCK_SIZE) >= BLOCK_SIZE);
+    assert!(max_decrypted_len(HEADER_SIZE + 3 * ENCRYPTED_BLOCK_SIZE) >= 3 * BLOCK_SIZE);
+    assert!(max_decrypted_len(3 * LEGACY_ENCRYPTED_BLOCK_SIZE) >= 3 * BLOCK_SIZE);
+
+    // The smallest blocks are 512 bytes packed with 16 bits each behind a seed.
+    assert_eq!(max_decrypted_len(1_000_000), 960 * 512);
+    assert!(max_decrypted_len(usize::MAX) < usize::MAX / 2);
+}
 
-    for i in 0..key_bytes1.length() {
-        key_bytes1.set_index(i, raw_key_bytes[i as usize]);
+#[test]
+fn decrypted_buffer_len_counts_blocks() {
+    let mut encrypted_data = vec![0; HEADER_SIZE + 2 * ENCRYPTED_BLOCK_SIZE];
+    Header {
+        params: Params::legacy(),
+        salt: [0; SALT_SIZE],
+        padding_len: 0,
     }
-    let key_bytes2 = Uint8Array::new_with_length(raw_key_bytes.len() as u32);
+    .write(&mut encrypted_data);
+    assert_eq!(decrypted_buffer_len(&encrypted_data), Ok(2 * BLOCK_SIZE));
+    assert_eq!(
+        decrypted_buffer_len(&encrypted_data[..HEADER_SIZE + 1]),
+        Err(Error::InvalidCiphertext)
+    );
+    assert_eq!(
+        decrypted_buffer_len(&[0; 2 * LEGACY_ENCRYPTED_BLOCK_SIZE]),
+        Ok(2 * BLOCK_SIZE)
+    );
+    assert_eq!(decrypted_buffer_len(&[]), Err(Error::InvalidCiphertext));
+}
+
+#[test]
+fn encrypt_into_rejects_short_output() {
+    let mut out = [0; ENCRYPTED_BLOCK_SIZE];
+    assert_eq!(
+        encrypt_into(&[1, 2, 3], &[4], &mut out),
+        Err(Error::OutputTooSmall {
+            required: header_size(&Params::new()) + ENCRYPTED_BLOCK_SIZE,
+            actual: ENCRYPTED_BLOCK_SIZE
+        })
+    );
+}
 
-    for i in 0..key_bytes2.length() {
-        key_bytes2.set_index(i, raw_key_bytes[i as usize]);
+#[test]
+fn decrypt_into_rejects_truncated_ciphertext() {
+    let mut out = [0; BLOCK_SIZE];
+    assert_eq!(
+        decrypt_into(&[], &[4], &mut out),
+        Err(Error::InvalidCiphertext)
+    );
+    assert_eq!(
+        decrypt_into(&[0; LEGACY_ENCRYPTED_BLOCK_SIZE - 1], &[4], &mut out),
+        Err(Error::InvalidCiphertext)
+    );
+
+    let mut encrypted_data = [0; HEADER_SIZE + ENCRYPTED_BLOCK_SIZE - 1];
+    Header {
+        params: Params::new(),
+        salt: [1; SALT_SIZE],
+        padding_len: 0,
     }
+    .write(&mut encrypted_data);
+    assert_eq!(
+        decrypt_into(&encrypted_data, &[4], &mut out),
+        Err(Error::InvalidCiphertext)
+    );
+}
+
+#[test]
+fn decrypt_into_rejects_short_output() {
+    let mut out = [0; BLOCK_SIZE];
+    assert_eq!(
+        decrypt_into(&[0; 2 * LEGACY_ENCRYPTED_BLOCK_SIZE], &[4], &mut out),
+        Err(Error::OutputTooSmall {
+            required: 2 * BLOCK_SIZE,
+            actual: BLOCK_SIZE
+        })
+    );
+}
 
-    let encrypted_data = encrypt(data, key_bytes1);
+#[test]
+fn encrypt_into_rejects_unsupported_params() {
+    let mut params = Params::new();
+    params.block_size = BlockSize::Bytes512;
+    let mut out = vec![0; params.encrypted_len(10).unwrap()];
+    assert_eq!(
+        params.encrypt_into(&[1; 10], &[1], &mut out),
+        Err(Error::UnsupportedParams)
+    );
+}
+
+/// A ciphertext together with the salt, IVs and padding it was encrypted with.
+#[derive(Deserialize)]
+struct KnownAnswerVector {
+    description: String,
+    /// Encoded with `Params::write`.
+    params: String,
+    key: String,
+    plaintext: String,
+    salt: String,
+    /// Every IV or seed drawn, including the rejected ones.
+    ivs: String,
+    padding: String,
+    ciphertext: String,
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn encrypt_matches_known_answer_vectors() {
+    let vectors: Vec<KnownAnswerVector> =
+        serde_json::from_str(include_str!("../tests/vectors/known_answers.json")).unwrap();
 
-    let decrypted_data = decrypt(encrypted_data, key_bytes2);
+    for vector in &vectors {
+        let params = Params::read(&from_hex(&vector.params)).unwrap();
+        let key_bytes = from_hex(&vector.key);
+        let data = from_hex(&vector.plaintext);
+        let (salt, ivs, padding) = (
+            from_hex(&vector.salt),
+            from_hex(&vector.ivs),
+            from_hex(&vector.padding),
+        );
+        let mut random = ReplayRandom::new(&salt, &ivs, &padding);
 
-    for (r, d) in raw_data.iter().zip(decrypted_data.to_vec().iter()) {
-        assert_eq!(r, d);
+        let mut encrypted_data = vec![0; params.encrypted_len(data.len()).unwrap()];
+        let encrypted_length =
+            encrypt_into_with_random(&params, &data, &key_bytes, &mut encrypted_data, &mut random)
+                .unwrap();
+        let expected = from_hex(&vector.ciphertext);
+        assert!(
+            encrypted_data[..encrypted_length] == expected[..],
+            "{}",
+            vector.description
+        );
+        assert!(random.is_exhausted(), "{}", vector.description);
+        assert_eq!(decrypt(&expected, &key_bytes).unwrap(), data);
     }
 }
 
-#[cfg(test)]
-mod tests {}
+/// A ciphertext produced by version 0.1.2, see `tests/vectors/README.md`.
+#[derive(Deserialize)]
+struct LegacyVector {
+    description: String,
+    key: String,
+    plaintext: String,
+    ciphertext: String,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn decrypt_reads_ciphertexts_of_0_1() {
+    let vectors: Vec<LegacyVector> =
+        serde_json::from_str(include_str!("../tests/vectors/legacy_0_1.json")).unwrap();
+
+    for vector in &vectors {
+        let encrypted_data = from_hex(&vector.ciphertext);
+        assert_eq!(encrypted_data.len() % LEGACY_ENCRYPTED_BLOCK_SIZE, 0);
+        assert_eq!(
+            decrypt(&encrypted_data, &from_hex(&vector.key)),
+            Ok(from_hex(&vector.plaintext)),
+            "{}",
+            vector.description
+        );
+    }
+}
+
+/// Messages of random lengths up to four blocks, with empty and single bytes and the lengths
+/// around the end of a block, where the padding changes, drawn more often. Failing messages
+/// shrink toward the shortest length of their range.
+#[cfg(not(target_arch = "wasm32"))]
+fn message() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        2 => vec(any::<u8>(), 0..4 * BLOCK_SIZE),
+        1 => vec(any::<u8>(), 0..=1),
+        1 => vec(any::<u8>(), BLOCK_SIZE - 5..=BLOCK_SIZE + 1),
+    ]
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn key() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..=64)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn supported_params() -> impl Strategy<Value = Params> {
+    (
+        prop_oneof![
+            Just(BlockSize::Bytes64),
+            Just(BlockSize::Bytes128),
+            Just(BlockSize::Bytes256),
+            Just(BlockSize::Bytes512),
+        ],
+        prop_oneof![Just(IvMode::Full), Just(IvMode::Seeded)],
+        prop_oneof![Just(ElementCodec::Fixed), Just(ElementCodec::Packed)],
+        prop_oneof![
+            Just(ElementWidth::Bytes5),
+            Just(ElementWidth::Bytes6),
+            Just(ElementWidth::Bytes8),
+        ],
+        prop_oneof![
+            Just(PaddingMode::Block),
+            Just(PaddingMode::Compact),
+            Just(PaddingMode::Embedded),
+        ],
+        prop_oneof![
+            Just(PaddingPolicy::WholeBlocks),
+            Just(PaddingPolicy::PowerOfTwo),
+            Just(PaddingPolicy::Padme),
+            Just(PaddingPolicy::Buckets),
+        ],
+    )
+        .prop_map(
+            |(block_size, iv_mode, element_codec, element_width, padding_mode, padding_policy)| {
+                Params {
+                    block_size,
+                    iv_mode,
+                    element_codec,
+                    element_width,
+                    padding_mode,
+                    padding_policy,
+                    bucket_size: 1024,
+                }
+            },
+        )
+        .prop_filter("parameters have to be supported", Params::is_supported)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+proptest! {
+    #[test]
+    fn decrypt_reverses_encrypt(data in message(), key_bytes in key()) {
+        let encrypted_data = encrypt(&data, &key_bytes).unwrap();
+        prop_assert_eq!(encrypted_data.len(), encrypted_len(data.len()).unwrap());
+        prop_assert_eq!(decrypt(&encrypted_data, &key_bytes).unwrap(), data);
+    }
+
+    #[test]
+    fn decrypt_into_reverses_encrypt_with_any_params(
+        params in supported_params(),
+        data in message(),
+        key_bytes in key(),
+    ) {
+        let encrypted_data = params.encrypt(&data, &key_bytes).unwrap();
+        prop_assert!(encrypted_data.len() <= params.encrypted_len(data.len()).unwrap());
+
+        let mut decrypted_data = vec![0; decrypted_buffer_len(&encrypted_data).unwrap()];
+        prop_assert!(decrypted_data.len() <= max_decrypted_len(encrypted_data.len()));
+        let decrypted_length = decrypt_into(&encrypted_data, &key_bytes, &mut decrypted_data).unwrap();
+        prop_assert_eq!(&decrypted_data[..decrypted_length], &data[..]);
+    }
+}
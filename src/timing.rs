@@ -0,0 +1,237 @@
+//! A dudect-style timing harness: every operation is timed on inputs of two classes, a fixed
+//! secret and random secrets, interleaved in random order, and Welch's t-test decides whether
+//! the two distributions of execution times differ.
+//!
+//! Every input, including the random bytes the operation draws, is generated before the
+//! timing starts, so only the operation itself is timed. Timings are only meaningful in
+//! optimized builds, so the tests are ignored and CI runs them in a step of their own with
+//! `cargo test --release --lib -- --ignored timing::`, with and without the `constant-time`
+//! feature. The rotation of the data by the key leaks its shift unless it is built with that
+//! feature, so without it the rotation test passes only if it detects the known leak.
+
+use crate::bit_cycling::{rotate_bits_left, rotate_bits_right};
+use crate::blocks::generation::derive_key;
+use crate::blocks::{decrypt_block, encrypt_block, BlockKeys, MAX_BLOCK_SIZE, SALT_SIZE};
+use crate::params::Params;
+use crate::random::ReplayRandom;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Number of timed runs of every operation.
+const MEASUREMENTS: usize = 20_000;
+/// Measurements slower than this fraction of all of them are dropped as interruptions.
+const CROP_PERCENTILE: f64 = 0.9;
+/// A `|t|` above this is a significant leak; dudect reports leaks from 4.5 up and
+/// certain ones from 10 up.
+const T_THRESHOLD: f64 = 10.0;
+
+/// Running means and variances of the execution times of both classes.
+#[derive(Default)]
+struct Welch {
+    count: [f64; 2],
+    mean: [f64; 2],
+    m2: [f64; 2],
+}
+
+impl Welch {
+    fn push(&mut self, class: usize, x: f64) {
+        self.count[class] += 1.0;
+        let delta = x - self.mean[class];
+        self.mean[class] += delta / self.count[class];
+        self.m2[class] += delta * (x - self.mean[class]);
+    }
+
+    fn t(&self) -> f64 {
+        let variance = |class: usize| self.m2[class] / (self.count[class] - 1.0);
+        let standard_error = (variance(0) / self.count[0] + variance(1) / self.count[1]).sqrt();
+        (self.mean[0] - self.mean[1]) / standard_error
+    }
+}
+
+/// Times `operation` on the inputs of `prepare` for class 0, a fixed secret, and class 1,
+/// random secrets, and returns Welch's t statistic of the cropped timings. All inputs are
+/// prepared before the first one is timed.
+fn t_statistic<I>(
+    mut prepare: impl FnMut(usize, &mut StdRng) -> I,
+    mut operation: impl FnMut(&mut I),
+) -> f64 {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut inputs: Vec<(usize, I)> = (0..MEASUREMENTS)
+        .map(|_| {
+            let class = rng.gen_range(0, 2);
+            (class, prepare(class, &mut rng))
+        })
+        .collect();
+
+    let timings: Vec<(usize, f64)> = inputs
+        .iter_mut()
+        .map(|(class, input)| {
+            let start = Instant::now();
+            operation(black_box(input));
+            (*class, start.elapsed().as_nanos() as f64)
+        })
+        .collect();
+
+    let mut sorted: Vec<f64> = timings.iter().map(|&(_, time)| time).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let crop = sorted[(CROP_PERCENTILE * sorted.len() as f64) as usize];
+
+    let mut welch = Welch::default();
+    for &(class, time) in timings.iter().filter(|&&(_, time)| time <= crop) {
+        welch.push(class, time);
+    }
+    welch.t()
+}
+
+fn assert_no_leak(name: &str, t: f64) {
+    assert!(
+        t.abs() < T_THRESHOLD,
+        "{} leaks timing: |t| = {:.1}",
+        name,
+        t.abs()
+    );
+}
+
+/// Checks that `name` leaks with the fast code paths, which it is known to, and that it does
+/// not with the `constant-time` feature.
+fn assert_leak_unless_constant_time(name: &str, t: f64) {
+    if cfg!(feature = "constant-time") {
+        assert_no_leak(name, t);
+    } else {
+        assert!(
+            t.abs() > T_THRESHOLD,
+            "{} is known to leak timing without `constant-time`, but |t| = {:.1}",
+            name,
+            t.abs()
+        );
+    }
+}
+
+/// Returns the derived key of a block for class 0, and a random one for class 1.
+fn derived_key(class: usize, rng: &mut StdRng) -> [u16; MAX_BLOCK_SIZE] {
+    let mut derived_key = [0; MAX_BLOCK_SIZE];
+    let key: [u8; 16] = if class == 0 { [0; 16] } else { rng.gen() };
+    BlockKeys::new(&key, &[0; SALT_SIZE]).derive(0, &mut derived_key);
+    derived_key
+}
+
+/// Returns random IVs for a block, with room for one rejected IV.
+fn ivs(rng: &mut StdRng) -> [u8; 2 * MAX_BLOCK_SIZE] {
+    let mut ivs = [0; 2 * MAX_BLOCK_SIZE];
+    rng.fill(&mut ivs[..]);
+    ivs
+}
+
+#[test]
+#[ignore]
+fn timing_of_encrypt_block_is_independent_of_the_plaintext() {
+    let params = Params::new();
+    let block_size = params.block_size.bytes();
+    let derived_key = derived_key(0, &mut StdRng::seed_from_u64(1));
+    let t = t_statistic(
+        |class, rng| {
+            let mut block = [0; MAX_BLOCK_SIZE];
+            if class == 1 {
+                rng.fill(&mut block[..]);
+            }
+            let encrypted_block = [0; 2 * MAX_BLOCK_SIZE + 8 * MAX_BLOCK_SIZE];
+            (block, ivs(rng), encrypted_block)
+        },
+        |(block, ivs, encrypted_block)| {
+            encrypt_block(
+                &block[..block_size],
+                &derived_key[..block_size],
+                &params,
+                encrypted_block,
+                &mut ReplayRandom::new(&[], &ivs[..], &[]),
+            )
+            .unwrap();
+        },
+    );
+    assert_no_leak("encrypt_block", t);
+}
+
+#[test]
+#[ignore]
+fn timing_of_decrypt_block_is_independent_of_the_key() {
+    let params = Params::new();
+    let block_size = params.block_size.bytes();
+    let t = t_statistic(
+        |class, rng| {
+            let derived_key = derived_key(class, rng);
+            let mut block = [0; MAX_BLOCK_SIZE];
+            rng.fill(&mut block[..block_size]);
+            let mut encrypted_block = [0; 2 * MAX_BLOCK_SIZE + 8 * MAX_BLOCK_SIZE];
+            encrypt_block(
+                &block[..block_size],
+                &derived_key[..block_size],
+                &params,
+                &mut encrypted_block,
+                &mut ReplayRandom::new(&[], &ivs(rng)[..], &[]),
+            )
+            .unwrap();
+            (derived_key, encrypted_block, block)
+        },
+        |(derived_key, encrypted_block, decrypted_block)| {
+            decrypt_block(
+                encrypted_block,
+                &derived_key[..block_size],
+                &params,
+                &mut decrypted_block[..block_size],
+            )
+            .unwrap();
+        },
+    );
+    assert_no_leak("decrypt_block", t);
+}
+
+#[test]
+#[ignore]
+fn timing_of_derive_key_is_independent_of_the_key() {
+    let t = t_statistic(
+        |class, rng| {
+            let key: [u8; 32] = if class == 0 { [0; 32] } else { rng.gen() };
+            (key, [0; MAX_BLOCK_SIZE])
+        },
+        |(key, derived_key)| derive_key(&key[..], &[0; SALT_SIZE], &mut derived_key[..]),
+    );
+    assert_no_leak("derive_key", t);
+}
+
+#[test]
+#[ignore]
+fn timing_of_bit_rotation_depends_on_the_shift_only_without_constant_time() {
+    let len = 4 * MAX_BLOCK_SIZE;
+    let shift = |class: usize, rng: &mut StdRng| {
+        if class == 0 {
+            0
+        } else {
+            rng.gen_range(0, 8 * len)
+        }
+    };
+    let t = t_statistic(
+        |class, rng| (vec![0x5a; len], shift(class, rng)),
+        |(data, bits_to_shift)| rotate_bits_right(data, *bits_to_shift),
+    );
+    assert_leak_unless_constant_time("rotate_bits_right", t);
+    let t = t_statistic(
+        |class, rng| (vec![0x5a; len], shift(class, rng)),
+        |(data, bits_to_shift)| rotate_bits_left(data, *bits_to_shift),
+    );
+    assert_leak_unless_constant_time("rotate_bits_left", t);
+}
+
+#[test]
+fn welch_t_tells_distributions_apart() {
+    let mut same = Welch::default();
+    let mut different = Welch::default();
+    for i in 0..1000 {
+        let x = (i / 2 % 10) as f64;
+        same.push(i % 2, x);
+        different.push(i % 2, x + 5.0 * (i % 2) as f64);
+    }
+    assert!(same.t().abs() < 1.0);
+    assert!(different.t().abs() > 10.0);
+}
@@ -0,0 +1,47 @@
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+#[inline]
+fn exchange_bits_right(a: u8, b: u8, bits_to_exchange: usize) -> i8 {
+    ((a << (8 - bits_to_exchange)) | (b >> bits_to_exchange)) as i8
+}
+
+pub fn cycle_right(data: &[u8], bits_to_shift: &BigUint, cycled_data: &mut Vec<i8>) {
+    let l1 = bits_to_shift % (8u64 * data.len() as u64);
+    let l2 = (&l1 % 8usize).to_usize().unwrap();
+    let l3 = (l1 / 8usize).to_usize().unwrap();
+
+    if l2 == 0 {
+        for k in 0..l3 {
+            cycled_data.push(data[data.len() + k - l3] as i8);
+        }
+
+        cycled_data.push(data[0] as i8);
+
+        for k in l3 + 1..data.len() {
+            cycled_data.push(data[k - l3] as i8);
+        }
+    } else {
+        for k in 0..l3 {
+            cycled_data.push(exchange_bits_right(
+                data[data.len() + k - l3 - 1],
+                data[data.len() + k - l3],
+                l2,
+            ));
+        }
+
+        cycled_data.push(exchange_bits_right(
+            data[data.len() - 1],
+            data[0],
+            l2,
+        ));
+
+        for k in l3 + 1..data.len() {
+            cycled_data.push(exchange_bits_right(
+                data[k - l3 - 1],
+                data[k - l3],
+                l2,
+            ));
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Prints a vector of `tests/vectors/legacy_0_1.json` as a line of JSON:
+//!
+//!     cargo run -- <key in hex> <plaintext length> [text]
+//!
+//! The plaintext is random, or repeats a sentence with `text`. The functions below are those of
+//! web-nse 0.1.2, commit 05a0b89, built natively: `crypto.getRandomValues` is replaced by
+//! `OsRng` and `Uint8Array` by slices. `cycle_right`, `PRIMES`, `are_orthogonal` and
+//! `vector_difference` are copied from that commit unchanged.
+mod bit_cycling;
+mod primes;
+mod vectors;
+
+use bit_cycling::cycle_right;
+use hkdf::Hkdf;
+use num_bigint::BigUint;
+use primes::PRIMES;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use vectors::{are_orthogonal, vector_difference};
+
+const BLOCK_SIZE: usize = 256;
+const SALT_SIZE: usize = 16;
+const ELEMENT_SIZE: usize = 5;
+const ENCRYPTED_BLOCK_SIZE: usize = ELEMENT_SIZE * BLOCK_SIZE + BLOCK_SIZE + SALT_SIZE;
+
+fn random(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn hash_number(number: &BigUint) -> BigUint {
+    let mut hasher = Sha256::default();
+    hasher.update(number.to_bytes_be());
+    BigUint::from_bytes_be(&hasher.finalize())
+}
+
+fn derive_key(key: &BigUint, salt: &[u8], derived_key: &mut [u16; BLOCK_SIZE]) {
+    let hkdf = Hkdf::<Sha256>::new(Some(salt), &key.to_bytes_be());
+    let mut okm = [0; BLOCK_SIZE];
+    hkdf.expand(&[], &mut okm).unwrap();
+    for i in 0..BLOCK_SIZE {
+        derived_key[i] = PRIMES[okm[i] as usize];
+    }
+}
+
+fn generate_iv(derived_key: &[u16; BLOCK_SIZE], block: &[i8], iv: &mut [i8; BLOCK_SIZE]) {
+    let mut difference = [0; BLOCK_SIZE];
+    while {
+        let unsigned_iv = random(BLOCK_SIZE);
+        for i in 0..BLOCK_SIZE {
+            iv[i] = unsigned_iv[i] as i8;
+        }
+        vector_difference(block, iv, &mut difference);
+        are_orthogonal(derived_key, &difference)
+    } {}
+}
+
+fn i40_as_u40(i40: i64) -> u64 {
+    let half = 1 << (8 * ELEMENT_SIZE - 1);
+    if i40 < 0 {
+        (i40 + 2 * half) as u64
+    } else {
+        i40 as u64
+    }
+}
+
+fn split_bytes<I: Iterator<Item = i64>>(data: &mut I, bytes: &mut [u8]) {
+    for i in (0..bytes.len()).step_by(ELEMENT_SIZE) {
+        let mut e = i40_as_u40(data.next().unwrap());
+        for byte in &mut bytes[i..i + ELEMENT_SIZE] {
+            *byte = (e & 255) as u8;
+            e >>= 8;
+        }
+    }
+}
+
+fn encrypt_block(block: &[i8], key: &BigUint, encrypted_block: &mut [u8]) {
+    let salt = random(SALT_SIZE);
+    encrypted_block[..SALT_SIZE].copy_from_slice(&salt);
+    let mut derived_key = [0; BLOCK_SIZE];
+    derive_key(key, &salt, &mut derived_key);
+    let mut iv = [0; BLOCK_SIZE];
+    generate_iv(&derived_key, block, &mut iv);
+    for i in SALT_SIZE..SALT_SIZE + BLOCK_SIZE {
+        encrypted_block[i] = iv[i - SALT_SIZE] as u8;
+    }
+
+    let mut sum1 = 0;
+    let mut sum2 = 0;
+    for i in 0..BLOCK_SIZE {
+        sum1 += derived_key[i] as i64 * derived_key[i] as i64;
+        sum2 += derived_key[i] as i64 * (block[i] as i64 - iv[i] as i64);
+    }
+    sum2 <<= 1;
+    let mut encrypted_elements = block
+        .iter()
+        .zip(derived_key.iter())
+        .map(|(&r, &p)| r as i64 * sum1 - p as i64 * sum2);
+    split_bytes(
+        &mut encrypted_elements,
+        &mut encrypted_block[SALT_SIZE + BLOCK_SIZE..ENCRYPTED_BLOCK_SIZE],
+    );
+}
+
+fn encrypt(data: &[u8], key_bytes: &[u8]) -> Vec<u8> {
+    let key = &BigUint::from_bytes_be(key_bytes);
+    let bytes_to_pad = (BLOCK_SIZE - (data.len() % BLOCK_SIZE)) % BLOCK_SIZE;
+    let mut padded_data = vec![0; data.len() + BLOCK_SIZE + bytes_to_pad];
+    padded_data[..data.len()].copy_from_slice(data);
+    let padding_bytes = random(padded_data.len() - data.len());
+    padded_data[data.len()..].copy_from_slice(&padding_bytes);
+
+    let last_index = padded_data.len() - 1;
+    padded_data[last_index] = bytes_to_pad as u8;
+    let bits_to_shift = hash_number(key);
+    let mut cycled_data = Vec::with_capacity(padded_data.len());
+    cycle_right(&padded_data, &bits_to_shift, &mut cycled_data);
+
+    let mut result = vec![0; cycled_data.len() / BLOCK_SIZE * ENCRYPTED_BLOCK_SIZE];
+    for i in (0..padded_data.len()).step_by(BLOCK_SIZE) {
+        let encrypted_block_start = i / BLOCK_SIZE * ENCRYPTED_BLOCK_SIZE;
+        encrypt_block(
+            &cycled_data[i..i + BLOCK_SIZE],
+            key,
+            &mut result[encrypted_block_start..encrypted_block_start + ENCRYPTED_BLOCK_SIZE],
+        );
+    }
+    result
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let key = from_hex(&args[1]);
+    let len: usize = args[2].parse().unwrap();
+    let data: Vec<u8> = if args.get(3).map(String::as_str) == Some("text") {
+        let sentence = b"The quick brown fox jumps over the lazy dog. ";
+        (0..len).map(|i| sentence[i % sentence.len()]).collect()
+    } else {
+        random(len)
+    };
+
+    println!(
+        "{{\"key\": \"{}\", \"plaintext\": \"{}\", \"ciphertext\": \"{}\"}}",
+        to_hex(&key),
+        to_hex(&data),
+        to_hex(&encrypt(&data, &key))
+    );
+}
@@ -0,0 +1,25 @@
+pub fn are_orthogonal(vector1: &[u16], vector2: &[i16]) -> bool {
+    if vector1.len() != vector2.len() {
+        panic!("vectors are of different size");
+    }
+    vector1
+        .iter()
+        .zip(vector2)
+        .fold(0i64, |acc, (&e1, &e2)| acc + e1 as i64 * e2 as i64)
+        == 0
+}
+
+pub fn vector_difference(vector1: &[i8], vector2: &[i8], output: &mut [i16]) {
+    let vector_size = vector1.len();
+    if vector_size != vector2.len() {
+        panic!("vectors are of different size");
+    }
+    if vector_size != output.len() {
+        panic!("output is of different size than vectors");
+    }
+
+    for i in 0..vector_size {
+        output[i] = vector1[i] as i16 - vector2[i] as i16;
+    }
+}
+